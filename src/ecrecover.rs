@@ -1,11 +1,15 @@
-use alloy_primitives::{address, Address, FixedBytes, U256};
+use alloy_primitives::{address, uint, Address, FixedBytes, U256};
 use alloy_sol_types::{sol, sol_data, SolType};
 use stylus_sdk::call::{self, Call};
 
-const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+/// The standard ECRECOVER precompile address.
+pub const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
 
-/// Invoke the ECRECOVER precompile.
+/// Invoke the ECRECOVER precompile deployed at `precompile`. This is
+/// [`ECRECOVER`] on most chains. Errors if the precompile returns less than a
+/// full word, as a misconfigured chain might.
 pub fn ecrecover(
+    precompile: Address,
     hash: FixedBytes<32>,
     v: u8,
     r: U256,
@@ -13,6 +17,116 @@ pub fn ecrecover(
 ) -> Result<Address, stylus_sdk::call::Error> {
     let data = <sol! { (bytes32, uint8, uint256, uint256) }>::encode(&(*hash, v, r, s));
 
-    call::static_call(Call::new(), ECRECOVER, &data)
-        .map(|ret| sol_data::Address::decode_single(ret.as_slice(), false).unwrap())
+    let ret = call::static_call(Call::new(), precompile, &data)?;
+    if ret.len() < 32 {
+        return Err(alloy_sol_types::Error::Overrun.into());
+    }
+    Ok(sol_data::Address::decode_single(&ret, false)?)
+}
+
+/// The order of the secp256k1 curve.
+pub const SECP256K1_ORDER: U256 =
+    uint!(0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141_U256);
+
+/// Splits a packed 64-byte `r ‖ s` signature into its components. Returns
+/// `None` for any other length, or unless both lie in `[1, n)` for the curve
+/// order `n`.
+pub fn split_rs(rs: &[u8]) -> Option<(U256, U256)> {
+    if rs.len() != 64 {
+        return None;
+    }
+    let r = U256::from_be_slice(&rs[..32]);
+    let s = U256::from_be_slice(&rs[32..]);
+    let valid = |x: U256| x != U256::ZERO && x < SECP256K1_ORDER;
+    (valid(r) && valid(s)).then_some((r, s))
+}
+
+/// Normalizes a signature's `v` to the 27/28 form ECRECOVER expects. Accepts
+/// raw parities (0/1), standard values (27/28) and EIP-155 values
+/// (`chainId * 2 + 35 + parity`). Returns `None` for anything else.
+///
+/// EIP-155 values only fit in a byte for chain ids up to 110, which excludes
+/// every Arbitrum chain. Use [`normalize_v_wide`] where `v` can be wider.
+pub fn normalize_v(v: u8) -> Option<u8> {
+    match v {
+        0 | 1 => Some(v + 27),
+        27 | 28 => Some(v),
+        35.. => Some(27 + (v - 35) % 2),
+        _ => None,
+    }
+}
+
+/// [`normalize_v`] for a `v` of any width, so EIP-155 values for any chain id
+/// are accepted.
+pub fn normalize_v_wide(v: U256) -> Option<u8> {
+    if v <= U256::from(u8::MAX) {
+        return normalize_v(v.to::<u8>());
+    }
+    Some(27 + ((v - U256::from(35)) % U256::from(2)).to::<u8>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_parities_and_standard_values() {
+        assert_eq!(normalize_v(0), Some(27));
+        assert_eq!(normalize_v(1), Some(28));
+        assert_eq!(normalize_v(27), Some(27));
+        assert_eq!(normalize_v(28), Some(28));
+    }
+
+    #[test]
+    fn normalizes_eip155_values() {
+        // Chain id 1: 37 and 38.
+        assert_eq!(normalize_v(37), Some(27));
+        assert_eq!(normalize_v(38), Some(28));
+        // Chain id 110 is the largest that fits in a byte.
+        assert_eq!(normalize_v(110 * 2 + 35), Some(27));
+    }
+
+    #[test]
+    fn rejects_other_values() {
+        for v in (2..27).chain(29..35) {
+            assert_eq!(normalize_v(v), None, "v = {v}");
+        }
+    }
+
+    #[test]
+    fn normalizes_wide_eip155_values() {
+        // Arbitrum One.
+        let base = U256::from(42161 * 2 + 35);
+        assert_eq!(normalize_v_wide(base), Some(27));
+        assert_eq!(normalize_v_wide(base + U256::from(1)), Some(28));
+        assert_eq!(normalize_v_wide(U256::from(27)), Some(27));
+        assert_eq!(normalize_v_wide(U256::from(2)), None);
+    }
+
+    fn packed(r: U256, s: U256) -> Vec<u8> {
+        [r.to_be_bytes::<32>(), s.to_be_bytes::<32>()].concat()
+    }
+
+    #[test]
+    fn splits_packed_signature() {
+        let (r, s) = (U256::from(1), SECP256K1_ORDER - U256::from(1));
+        assert_eq!(split_rs(&packed(r, s)), Some((r, s)));
+    }
+
+    #[test]
+    fn rejects_other_lengths() {
+        let rs = packed(U256::from(1), U256::from(2));
+        assert_eq!(split_rs(&rs[..63]), None);
+        assert_eq!(split_rs(&[rs.clone(), vec![0]].concat()), None);
+        assert_eq!(split_rs(&[]), None);
+    }
+
+    #[test]
+    fn rejects_out_of_range_components() {
+        let one = U256::from(1);
+        assert_eq!(split_rs(&packed(U256::ZERO, one)), None);
+        assert_eq!(split_rs(&packed(one, U256::ZERO)), None);
+        assert_eq!(split_rs(&packed(SECP256K1_ORDER, one)), None);
+        assert_eq!(split_rs(&packed(one, SECP256K1_ORDER)), None);
+    }
 }
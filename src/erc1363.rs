@@ -0,0 +1,79 @@
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::call::{self, Call};
+
+sol! {
+    function onTransferReceived(address operator, address from, uint256 value, bytes data) external returns (bytes4);
+    function onApprovalReceived(address owner, uint256 value, bytes data) external returns (bytes4);
+}
+
+/// Notify an ERC-1363 receiver of a transfer. Returns whether the receiver
+/// replied with the expected magic value.
+pub fn on_transfer_received(
+    receiver: Address,
+    operator: Address,
+    from: Address,
+    value: U256,
+    data: Vec<u8>,
+) -> Result<bool, stylus_sdk::call::Error> {
+    let calldata = onTransferReceivedCall {
+        operator,
+        from,
+        value,
+        data,
+    }
+    .encode();
+
+    call::call(Call::new(), receiver, &calldata)
+        .map(|ret| is_magic(&ret, onTransferReceivedCall::SELECTOR))
+}
+
+/// Notify an ERC-1363 spender of an approval. Returns whether the spender
+/// replied with the expected magic value.
+pub fn on_approval_received(
+    spender: Address,
+    owner: Address,
+    value: U256,
+    data: Vec<u8>,
+) -> Result<bool, stylus_sdk::call::Error> {
+    let calldata = onApprovalReceivedCall { owner, value, data }.encode();
+
+    call::call(Call::new(), spender, &calldata)
+        .map(|ret| is_magic(&ret, onApprovalReceivedCall::SELECTOR))
+}
+
+/// The magic value is the callback's own selector, left-aligned in the
+/// returned word.
+fn is_magic(ret: &[u8], selector: [u8; 4]) -> bool {
+    ret.len() >= 32 && ret[..4] == selector
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECTOR: [u8; 4] = [0x88, 0xa7, 0xca, 0x5c];
+
+    fn word(prefix: &[u8]) -> Vec<u8> {
+        let mut word = vec![0u8; 32];
+        word[..prefix.len()].copy_from_slice(prefix);
+        word
+    }
+
+    #[test]
+    fn accepts_left_aligned_selector() {
+        assert!(is_magic(&word(&SELECTOR), SELECTOR));
+    }
+
+    #[test]
+    fn rejects_other_words() {
+        assert!(!is_magic(&word(&[0x88, 0xa7, 0xca, 0x5d]), SELECTOR));
+        assert!(!is_magic(&word(&[]), SELECTOR));
+    }
+
+    #[test]
+    fn rejects_short_returns() {
+        assert!(!is_magic(&SELECTOR, SELECTOR));
+        assert!(!is_magic(&[], SELECTOR));
+    }
+}
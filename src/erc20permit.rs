@@ -1,10 +1,15 @@
-use std::marker::PhantomData;
+use std::{
+    borrow::{BorrowMut, Cow},
+    marker::PhantomData,
+};
 
-use alloy_primitives::{Address, FixedBytes, U256};
+use alloy_primitives::{keccak256, Address, FixedBytes, U256, U64, U8};
 use alloy_sol_types::{sol, Eip712Domain, SolError, SolStruct};
 use stylus_sdk::{
+    abi::{Bytes, Router},
     block::{self, chainid},
-    contract, msg,
+    call, contract, evm, msg,
+    storage::TopLevelStorage,
     stylus_proc::{external, sol_storage},
 };
 
@@ -13,6 +18,42 @@ pub trait DomainInfo {
     const NAME: Option<&'static str>;
     const VERSION: Option<&'static str>;
     const SALT: Option<FixedBytes<32>>;
+
+    /// Address of the ECRECOVER precompile used to verify signatures.
+    /// Override for chains or test harnesses that relocate precompiles.
+    const ECRECOVER: Address = ecrecover::ECRECOVER;
+
+    /// When `NAME` is `None`, use the token's `Erc20Details::NAME` as the
+    /// domain name instead of omitting it. Many signing libraries always
+    /// include a name.
+    const NAME_FALLBACK: bool = false;
+
+    /// When set, signed deadlines more than this many seconds in the future
+    /// are rejected, bounding the lifetime of leaked signatures.
+    const MAX_DEADLINE_WINDOW: Option<u64> = None;
+
+    /// When set, owners may register a salt of their own, which is folded
+    /// into the domain salt for the signatures they make. This partitions
+    /// signatures per app in meta-transaction setups.
+    const PER_OWNER_SALT: bool = false;
+
+    /// When set, permits to a spender consume a nonce kept per
+    /// `(owner, spender)` rather than the owner's shared nonce, so permits
+    /// signed concurrently for different spenders don't invalidate each
+    /// other. `permit_multi` still uses the shared nonce.
+    const PER_SPENDER_NONCES: bool = false;
+
+    /// The domain name. Defaults to `NAME`; override to supply it from
+    /// elsewhere, e.g. the token's stored `name`. The domain separator is
+    /// cached per chain id, so the name must not change while deployed on a
+    /// given chain.
+    fn domain_name<U>(_token: &Erc20Permit<Self, U>) -> Option<Cow<'static, str>>
+    where
+        Self: Sized,
+        U: Erc20Details + TransferHook + TransferFee,
+    {
+        Self::NAME.map(Cow::Borrowed)
+    }
 }
 
 /// Erc20 details.
@@ -20,6 +61,99 @@ pub trait Erc20Details {
     const NAME: &'static str;
     const SYMBOL: &'static str;
     const DECIMALS: u8;
+
+    /// When set, `name`, `symbol` and `decimals` are read from storage instead
+    /// of the consts above. The owner may change the name and symbol at any
+    /// time, and set the decimals once. Until then, `DECIMALS` applies.
+    const MUTABLE_METADATA: bool = false;
+
+    /// When set, balances are stored as shares of a global index that the
+    /// owner may `rebase`. Balances are `shares * index / REBASE_PRECISION`.
+    const REBASING: bool = false;
+
+    /// When set, `_mint` errors once this much has been minted in the
+    /// current block, limiting the damage a compromised minter can do.
+    const MAX_MINT_PER_BLOCK: Option<U256> = None;
+
+    /// When set, anyone may deposit this ERC20 via `mint_with_permit` to mint
+    /// the same amount of this token.
+    const COLLATERAL: Option<Address> = None;
+
+    /// Addresses tokens are burned by sending to, such as `0x…dead`. Their
+    /// balances are excluded from `circulating_supply`.
+    const BURN_SINKS: &'static [Address] = &[];
+
+    /// When nonzero, the last this-many allowance changes per owner and
+    /// spender are kept in a ring buffer for `last_allowance_changes`.
+    const ALLOWANCE_HISTORY_LEN: u64 = 0;
+
+    /// When set, every new allowance expires this many seconds after it is
+    /// granted, unless a permit sets its own expiration.
+    const DEFAULT_ALLOWANCE_TTL: Option<u64> = None;
+
+    /// How many blocks migration mode stays open once the owner enables it.
+    /// Zero disables migration mode entirely.
+    const MIGRATION_WINDOW_BLOCKS: u64 = 0;
+
+    /// When set, owner-only calls must be queued and may only be executed
+    /// through `execute` once this many seconds have passed.
+    const TIMELOCK_DELAY: Option<u64> = None;
+
+    /// When set, `approve`, `approve_with_deadline` and `approve_and_call`
+    /// add to the existing allowance instead of overwriting it, and
+    /// `approve(spender, 0)` clears it. This breaks integrations that approve
+    /// an exact amount expecting ERC-20 semantics, so leave it off unless
+    /// every spender is known to expect it; standard tokens offer
+    /// `increase_allowance` for the additive case. Permits always set the
+    /// allowance absolutely.
+    const APPROVE_IS_CUMULATIVE: bool = false;
+
+    /// The only account that may call `init`, e.g. the deployer or a factory.
+    /// Until it does, anyone else could initialize the token and take its
+    /// supply, so deploy and initialize atomically. `None` disables `init`
+    /// for contracts that initialize through `_init` themselves.
+    const INITIALIZER: Option<Address> = None;
+}
+
+/// Fixed-point precision of the rebase index. An index of this value means
+/// one share is worth one token.
+pub const REBASE_PRECISION: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+/// Transfer fee configuration. Fees are taken from the transferred amount
+/// and credited to the `fee_recipient` set by the owner. No fee is taken
+/// while the recipient is unset.
+pub trait TransferFee {
+    /// Fee in basis points of each transfer.
+    const TRANSFER_FEE_BPS: u16 = 0;
+}
+
+/// Hooks run around every balance change, including mints (`from` is zero)
+/// and burns (`to` is zero). Both default to no-ops. They are handed the
+/// token, so they can read its state and keep their own in `hook_data`.
+pub trait TransferHook: Sized {
+    fn before_token_transfer<T: DomainInfo>(
+        _token: &mut Erc20Permit<T, Self>,
+        _from: Address,
+        _to: Address,
+        _amount: U256,
+    ) -> Erc20Result<()>
+    where
+        Self: Erc20Details + TransferFee,
+    {
+        Ok(())
+    }
+
+    fn after_token_transfer<T: DomainInfo>(
+        _token: &mut Erc20Permit<T, Self>,
+        _from: Address,
+        _to: Address,
+        _amount: U256,
+    ) -> Erc20Result<()>
+    where
+        Self: Erc20Details + TransferFee,
+    {
+        Ok(())
+    }
 }
 
 sol_storage! {
@@ -27,8 +161,69 @@ sol_storage! {
         mapping (address => uint256) balances;
         uint256 total_supply;
         mapping (address => mapping(address => uint256)) allowances;
+        mapping (address => mapping(address => uint64)) allowance_expirations;
+        mapping (address => mapping(address => uint256)) spend_caps;
+
+        mapping (address => uint256) account_data;
+        mapping (address => mapping(uint256 => uint256)) nonce_bitmaps;
+        mapping (address => mapping(bytes32 => bool)) authorization_states;
+
+        bytes32 cached_domain_separator;
+        uint64 cached_chain_id;
+
+        bool initialized;
+        bool locked;
+        address owner;
+        address pending_owner;
+
+        string name;
+        string symbol;
+        uint8 decimals;
+        bool decimals_set;
+
+        address fee_recipient;
+
+        uint256 rebase_index;
+
+        uint64 mint_block;
+        uint256 minted_in_block;
+
+        mapping (address => uint256) frozen;
+
+        uint256 holder_count;
+
+        mapping (address => mapping(address => bool)) operators;
+
+        uint64[] checkpoint_blocks;
+        uint256[] checkpoint_supplies;
+
+        mapping (address => mapping(address => uint64)) allowance_change_counts;
+        mapping (address => mapping(address => mapping(uint256 => uint256))) allowance_change_amounts;
+        mapping (address => mapping(address => mapping(uint256 => uint64))) allowance_change_blocks;
+
+        bytes32 domain_salt;
+        bool domain_salt_set;
+        uint64 migration_end_block;
+        bool migration_used;
+
+        mapping (address => bytes32) owner_salts;
+
+        mapping (bytes32 => uint64) timelock_etas;
+        bool timelock_executing;
+
+        mapping (address => uint256) credits;
+
+        mapping (address => mapping (address => uint256)) spender_nonces;
+
+        mapping (address => uint256) approval_counts;
 
-        mapping (address => uint256) nonces;
+        mapping (bytes32 => uint256) hook_data;
+
+        // Reserved slots so fields can be added without shifting the storage
+        // of contracts that embed this one, e.g. behind an upgradeable proxy.
+        // New fields go above the gap, which must shrink by the slots they
+        // take.
+        uint256[38] __gap;
 
         PhantomData<T> domain;
         PhantomData<U> details;
@@ -44,34 +239,420 @@ sol! {
         uint256 deadline;
     }
 
+    struct PermitWithExpiry {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 nonce;
+        uint256 deadline;
+        uint64 expiration;
+    }
+
+    struct PermitWithCap {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 maxSpendPerTx;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct PermitMulti {
+        address owner;
+        address[] spenders;
+        uint256[] values;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct PermitWithRelayerFee {
+        address owner;
+        address spender;
+        uint256 value;
+        address relayer;
+        uint256 relayerFee;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct RevokeApproval {
+        address owner;
+        address spender;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct PermitBlockDeadline {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 nonce;
+        uint256 deadlineBlock;
+    }
+
+    struct UnorderedPermit {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct PermitIncrease {
+        address owner;
+        address spender;
+        uint256 addedValue;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct Swap {
+        address maker;
+        uint256 makerAmount;
+        address taker;
+        address takerToken;
+        uint256 takerAmount;
+        uint256 nonce;
+        uint256 deadline;
+    }
+
+    struct TransferWithAuthorization {
+        address from;
+        address to;
+        uint256 value;
+        uint256 validAfter;
+        uint256 validBefore;
+        bytes32 nonce;
+    }
+
+    struct ReceiveWithAuthorization {
+        address from;
+        address to;
+        uint256 value;
+        uint256 validAfter;
+        uint256 validBefore;
+        bytes32 nonce;
+    }
+
+    struct CancelAuthorization {
+        address authorizer;
+        bytes32 nonce;
+    }
+
     contract Erc20 {
-        #[derive(Default)]
-        error PermitExpired();
+        error PermitExpired(uint256 deadline, uint256 blockTimestamp);
+        error PermitExpiredAtBlock(uint256 deadlineBlock, uint256 blockNumber);
         #[derive(Default)]
         error InvalidPermit();
         #[derive(Default)]
+        error InvalidOwner();
+        error PermitError(uint8 code);
+        #[derive(Default)]
+        error DeadlineTooFar();
+        #[derive(Default)]
+        error AuthorizationNotYetValid();
+        #[derive(Default)]
+        error AuthorizationExpired();
+        #[derive(Default)]
+        error AuthorizationAlreadyUsed();
+        #[derive(Default)]
+        error NonceAlreadyUsed();
+        #[derive(Default)]
         error InsufficientBalance();
         #[derive(Default)]
         error InsufficientAllowance();
+        #[derive(Default)]
+        error AllowanceNotExpired();
+        #[derive(Default)]
+        error AllowanceOverflow();
+        error InvalidDeadline(uint64 deadline, uint64 blockTimestamp);
+        #[derive(Default)]
+        error AllowanceUnderflow();
+        #[derive(Default)]
+        error SpendCapExceeded();
+        #[derive(Default)]
+        error AllowanceDecrease();
+        #[derive(Default)]
+        error Unauthorized();
+        #[derive(Default)]
+        error RenounceNotConfirmed();
+        #[derive(Default)]
+        error MetadataImmutable();
+        #[derive(Default)]
+        error AlreadyInitialized();
+        #[derive(Default)]
+        error Reentrancy();
+        error ReceiverRejected(address receiver);
+        #[derive(Default)]
+        error LengthMismatch();
+        #[derive(Default)]
+        error BalanceOverflow();
+        #[derive(Default)]
+        error TransferTooSmall();
+        #[derive(Default)]
+        error MintRateExceeded();
+        #[derive(Default)]
+        error InvalidRebase();
+        #[derive(Default)]
+        error CannotRescueSelf();
+        error TokenCallFailed(address token);
+        error EthTransferFailed(address to);
+        error UnknownSelector(bytes4 selector);
+        #[derive(Default)]
+        error MigrationInactive();
+        #[derive(Default)]
+        error TimelockRequired();
+        #[derive(Default)]
+        error CallNotQueued();
+        #[derive(Default)]
+        error CallNotReady();
+        #[derive(Default)]
+        error NoCollateral();
+        #[derive(Default)]
+        error BalanceFrozen();
 
         event Transfer(address indexed from, address indexed to, uint256 amount);
 
         event Approval(address indexed owner, address indexed spender, uint256 amount);
+
+        event Mint(address indexed to, uint256 amount);
+
+        event Burn(address indexed from, uint256 amount);
+
+        event Rebase(uint256 oldIndex, uint256 newIndex);
+
+        event OwnershipTransferStarted(address indexed previousOwner, address indexed newOwner);
+
+        event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
+
+        event AuthorizationUsed(address indexed authorizer, bytes32 indexed nonce);
+
+        event AuthorizationCanceled(address indexed authorizer, bytes32 indexed nonce);
+
+        event EIP712DomainChanged();
+
+        event MigrationModeEnabled(uint64 endBlock);
+
+        event CallQueued(bytes32 indexed id, uint64 eta);
+
+        event CallExecuted(bytes32 indexed id);
+
+        event CallCanceled(bytes32 indexed id);
+
+        event PermitUsed(address indexed owner, address indexed spender, uint256 value, uint256 nonce);
+
+        event AuthorizedOperator(address indexed operator, address indexed holder);
+
+        event RevokedOperator(address indexed operator, address indexed holder);
     }
 }
 
 use Erc20::Erc20Errors;
 
-use crate::ecrecover::ecrecover;
-type Erc20Result<T> = Result<T, Erc20Errors>;
+use crate::{
+    ecrecover::{self, ecrecover, normalize_v, normalize_v_wide, split_rs},
+    erc1363,
+    math::{checked_mul_div_floor, mul_div_ceil, mul_div_floor},
+    token,
+};
+pub type Erc20Result<T> = Result<T, Erc20Errors>;
+
+/// Width of the permit nonce within an account's packed data. The remaining
+/// high bits hold flags.
+const NONCE_BITS: usize = 192;
+
+fn nonce_mask() -> U256 {
+    (U256::from(1) << NONCE_BITS) - U256::from(1)
+}
+
+/// ERC-165 interface ids reported by `supports_interface`.
+const ERC165_INTERFACE_ID: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+const ERC20_INTERFACE_ID: [u8; 4] = [0x36, 0x37, 0x2b, 0x07];
+/// The ERC-2612 interface id, for contracts that route its canonical
+/// selectors.
+pub const ERC2612_INTERFACE_ID: [u8; 4] = [0x9d, 0x8f, 0xf7, 0xda];
+
+/// `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(data))`
+fn eip712_digest<S: SolStruct>(separator: FixedBytes<32>, data: &S) -> FixedBytes<32> {
+    eip712_digest_of(separator, data.eip712_hash_struct())
+}
+
+/// `eip712_digest` for an already-computed `hashStruct`.
+fn eip712_digest_of(separator: FixedBytes<32>, struct_hash: FixedBytes<32>) -> FixedBytes<32> {
+    let mut digest_input = [0u8; 2 + 32 + 32];
+    digest_input[0] = 0x19;
+    digest_input[1] = 0x01;
+    digest_input[2..34].copy_from_slice(&separator[..]);
+    digest_input[34..66].copy_from_slice(&struct_hash[..]);
+    keccak256(digest_input)
+}
+
+/// Whether `supports_interface` reports `interface_id`.
+fn is_supported_interface(interface_id: [u8; 4]) -> bool {
+    matches!(interface_id, ERC165_INTERFACE_ID | ERC20_INTERFACE_ID)
+}
+
+/// `PERMIT_TYPEHASH`: the hash of `Permit`'s EIP-712 type string.
+fn permit_typehash() -> FixedBytes<32> {
+    keccak256(Permit::eip712_encode_type().as_bytes())
+}
+
+/// `check_deadline` at block timestamp `now`, with an optional
+/// `MAX_DEADLINE_WINDOW`.
+fn check_deadline_at(deadline: U256, now: U256, window: Option<u64>) -> Erc20Result<()> {
+    if now > deadline {
+        return Err(Erc20::Erc20Errors::PermitExpired(Erc20::PermitExpired {
+            deadline,
+            blockTimestamp: now,
+        }));
+    }
+    if let Some(window) = window {
+        if deadline > now + U256::from(window) {
+            return Err(Erc20::Erc20Errors::DeadlineTooFar(Default::default()));
+        }
+    }
+    Ok(())
+}
+
+/// Sorts `(recipient, amount)` pairs by recipient, summing the amounts of
+/// duplicates. A sum that overflows can't be covered by any balance.
+fn merge_transfers(
+    recipients: Vec<Address>,
+    amounts: Vec<U256>,
+) -> Erc20Result<Vec<(Address, U256)>> {
+    let mut pairs: Vec<(Address, U256)> = recipients.into_iter().zip(amounts).collect();
+    pairs.sort_unstable_by_key(|(to, _)| *to);
+
+    let mut merged: Vec<(Address, U256)> = Vec::with_capacity(pairs.len());
+    for (to, amount) in pairs {
+        match merged.last_mut() {
+            Some((last, sum)) if *last == to => {
+                *sum = sum
+                    .checked_add(amount)
+                    .ok_or(Erc20::Erc20Errors::InsufficientBalance(Default::default()))?;
+            }
+            _ => merged.push((to, amount)),
+        }
+    }
+    Ok(merged)
+}
+
+/// `to_whole_units` for a token with `decimals` decimals.
+fn whole_units(amount: U256, decimals: u8) -> (U256, U256) {
+    match U256::from(10).checked_pow(U256::from(decimals)) {
+        Some(unit) => amount.div_rem(unit),
+        None => (U256::ZERO, amount),
+    }
+}
+
+/// Rejects a zero owner or spender before a permit's signature is touched,
+/// so the owner keeps their nonce. A zero spender is always a mistake.
+fn check_permit_parties(owner: Address, spenders: &[Address]) -> Erc20Result<()> {
+    if owner == Address::ZERO {
+        return Err(Erc20::Erc20Errors::InvalidOwner(Default::default()));
+    }
+    if spenders.contains(&Address::ZERO) {
+        return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+    }
+    Ok(())
+}
+
+/// The account `permit_multisig` acts for: the low 20 bytes of
+/// `keccak256(abi.encode(owner_set, threshold))`.
+fn multisig_account(owner_set: &[Address], threshold: u8) -> Address {
+    type MultisigKey = sol! { (address[], uint8) };
+    let encoded =
+        <MultisigKey as alloy_sol_types::SolType>::encode(&(owner_set.to_vec(), threshold));
+    Address::from_word(keccak256(encoded))
+}
+
+/// The encoded `UnknownSelector` error for calldata that is too short to
+/// hold a selector or names no method. The selector is zero-padded.
+pub fn unknown_selector(calldata: &[u8]) -> Vec<u8> {
+    let mut selector = [0u8; 4];
+    let len = calldata.len().min(4);
+    selector[..len].copy_from_slice(&calldata[..len]);
+    Erc20Errors::UnknownSelector(Erc20::UnknownSelector { selector }).encode()
+}
+
+/// Codes carried by `PermitError`, which stands in for the granular permit
+/// errors unless the `granular-permit-errors` feature is enabled.
+pub mod permit_error {
+    /// `PermitExpired` or `PermitExpiredAtBlock`: the deadline has passed.
+    pub const EXPIRED: u8 = 1;
+    /// `DeadlineTooFar`: the deadline exceeds `MAX_DEADLINE_WINDOW`.
+    pub const DEADLINE_TOO_FAR: u8 = 2;
+    /// `InvalidPermit`: the signature is malformed or not by the owner, or a
+    /// spender is the zero address.
+    pub const INVALID_SIGNATURE: u8 = 3;
+    /// `InvalidOwner`: the owner is the zero address.
+    pub const INVALID_OWNER: u8 = 4;
+    /// `NonceAlreadyUsed`: the unordered nonce was used or canceled.
+    pub const NONCE_USED: u8 = 5;
+}
 
 impl Erc20Errors {
-    fn encode(&self) -> Vec<u8> {
+    /// The `permit_error` code for permit failures.
+    fn permit_error_code(&self) -> Option<u8> {
+        match self {
+            Erc20Errors::PermitExpired(_) | Erc20Errors::PermitExpiredAtBlock(_) => {
+                Some(permit_error::EXPIRED)
+            }
+            Erc20Errors::DeadlineTooFar(_) => Some(permit_error::DEADLINE_TOO_FAR),
+            Erc20Errors::InvalidPermit(_) => Some(permit_error::INVALID_SIGNATURE),
+            Erc20Errors::InvalidOwner(_) => Some(permit_error::INVALID_OWNER),
+            Erc20Errors::NonceAlreadyUsed(_) => Some(permit_error::NONCE_USED),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        if !cfg!(feature = "granular-permit-errors") {
+            if let Some(code) = self.permit_error_code() {
+                return Erc20::PermitError { code }.encode();
+            }
+        }
+
         match self {
             Erc20Errors::PermitExpired(e) => e.encode(),
+            Erc20Errors::PermitExpiredAtBlock(e) => e.encode(),
             Erc20Errors::InvalidPermit(e) => e.encode(),
+            Erc20Errors::InvalidOwner(e) => e.encode(),
+            Erc20Errors::PermitError(e) => e.encode(),
+            Erc20Errors::DeadlineTooFar(e) => e.encode(),
+            Erc20Errors::AuthorizationNotYetValid(e) => e.encode(),
+            Erc20Errors::AuthorizationExpired(e) => e.encode(),
+            Erc20Errors::AuthorizationAlreadyUsed(e) => e.encode(),
+            Erc20Errors::NonceAlreadyUsed(e) => e.encode(),
             Erc20Errors::InsufficientBalance(e) => e.encode(),
             Erc20Errors::InsufficientAllowance(e) => e.encode(),
+            Erc20Errors::AllowanceNotExpired(e) => e.encode(),
+            Erc20Errors::AllowanceOverflow(e) => e.encode(),
+            Erc20Errors::InvalidDeadline(e) => e.encode(),
+            Erc20Errors::AllowanceUnderflow(e) => e.encode(),
+            Erc20Errors::SpendCapExceeded(e) => e.encode(),
+            Erc20Errors::AllowanceDecrease(e) => e.encode(),
+            Erc20Errors::Unauthorized(e) => e.encode(),
+            Erc20Errors::RenounceNotConfirmed(e) => e.encode(),
+            Erc20Errors::MetadataImmutable(e) => e.encode(),
+            Erc20Errors::AlreadyInitialized(e) => e.encode(),
+            Erc20Errors::Reentrancy(e) => e.encode(),
+            Erc20Errors::ReceiverRejected(e) => e.encode(),
+            Erc20Errors::LengthMismatch(e) => e.encode(),
+            Erc20Errors::BalanceOverflow(e) => e.encode(),
+            Erc20Errors::TransferTooSmall(e) => e.encode(),
+            Erc20Errors::MintRateExceeded(e) => e.encode(),
+            Erc20Errors::InvalidRebase(e) => e.encode(),
+            Erc20Errors::CannotRescueSelf(e) => e.encode(),
+            Erc20Errors::TokenCallFailed(e) => e.encode(),
+            Erc20Errors::EthTransferFailed(e) => e.encode(),
+            Erc20Errors::UnknownSelector(e) => e.encode(),
+            Erc20Errors::MigrationInactive(e) => e.encode(),
+            Erc20Errors::TimelockRequired(e) => e.encode(),
+            Erc20Errors::CallNotQueued(e) => e.encode(),
+            Erc20Errors::CallNotReady(e) => e.encode(),
+            Erc20Errors::NoCollateral(e) => e.encode(),
+            Erc20Errors::BalanceFrozen(e) => e.encode(),
         }
     }
 }
@@ -80,24 +661,98 @@ impl Erc20Errors {
 impl<T, U> Erc20Permit<T, U>
 where
     T: DomainInfo,
-    U: Erc20Details,
+    U: Erc20Details + TransferHook + TransferFee,
 {
-    pub fn name() -> Result<String, Vec<u8>> {
-        Ok(U::NAME.to_owned())
+    /// One-time initializer. Mints the initial supply and sets the owner.
+    /// Only `INITIALIZER` may call this.
+    pub fn init(
+        &mut self,
+        initial_holder: Address,
+        initial_supply: U256,
+        owner: Address,
+    ) -> Result<(), Vec<u8>> {
+        if U::INITIALIZER != Some(msg::sender()) {
+            return Err(Erc20Errors::Unauthorized(Default::default()).encode());
+        }
+        self._init(initial_holder, initial_supply, owner)
+            .map_err(|e| e.encode())
+    }
+
+    pub fn owner(&self) -> Result<Address, Vec<u8>> {
+        Ok(self.owner.get())
+    }
+
+    pub fn pending_owner(&self) -> Result<Address, Vec<u8>> {
+        Ok(self.pending_owner.get())
+    }
+
+    /// Starts a two-step ownership transfer. The current owner keeps control
+    /// until `new_owner` calls `accept_ownership`.
+    pub fn transfer_ownership(&mut self, new_owner: Address) -> Result<(), Vec<u8>> {
+        self._transfer_ownership(new_owner).map_err(|e| e.encode())
+    }
+
+    pub fn accept_ownership(&mut self) -> Result<(), Vec<u8>> {
+        self._accept_ownership().map_err(|e| e.encode())
+    }
+
+    /// Permanently gives up ownership, disabling every owner-gated method.
+    /// `confirm` must be `true`, to guard against accidental calls.
+    pub fn renounce_ownership(&mut self, confirm: bool) -> Result<(), Vec<u8>> {
+        self._renounce_ownership(confirm).map_err(|e| e.encode())
+    }
+
+    pub fn name(&self) -> Result<String, Vec<u8>> {
+        Ok(self._name())
+    }
+
+    pub fn symbol(&self) -> Result<String, Vec<u8>> {
+        Ok(self._symbol())
+    }
+
+    pub fn set_name(&mut self, name: String) -> Result<(), Vec<u8>> {
+        self._set_name(name).map_err(|e| e.encode())
+    }
+
+    pub fn set_symbol(&mut self, symbol: String) -> Result<(), Vec<u8>> {
+        self._set_symbol(symbol).map_err(|e| e.encode())
+    }
+
+    pub fn decimals(&self) -> Result<u8, Vec<u8>> {
+        Ok(self._decimals())
     }
 
-    pub fn symbol() -> Result<String, Vec<u8>> {
-        Ok(U::SYMBOL.to_owned())
+    /// Splits `amount` into whole tokens and the remaining fractional base
+    /// units, according to `decimals`. With zero decimals everything is
+    /// whole; with more than 77, no `U256` amount reaches one whole token.
+    pub fn to_whole_units(&self, amount: U256) -> Result<(U256, U256), Vec<u8>> {
+        Ok(whole_units(amount, self._decimals()))
     }
 
-    pub fn decimals() -> Result<u8, Vec<u8>> {
-        Ok(U::DECIMALS)
+    /// Sets the decimals of a `MUTABLE_METADATA` token. May only be called
+    /// once, and only by the owner.
+    pub fn set_decimals(&mut self, decimals: u8) -> Result<(), Vec<u8>> {
+        self._set_decimals(decimals).map_err(|e| e.encode())
     }
 
     pub fn total_supply(&self) -> Result<U256, Vec<u8>> {
         Ok(self._total_supply())
     }
 
+    /// The total supply less balances held at `BURN_SINKS`.
+    pub fn circulating_supply(&self) -> Result<U256, Vec<u8>> {
+        let burned = U::BURN_SINKS
+            .iter()
+            .fold(U256::ZERO, |sum, sink| sum + self._balance_of(*sink));
+        Ok(self._total_supply().saturating_sub(burned))
+    }
+
+    /// The total supply as of the end of block `block_number`, or zero before
+    /// the first mint.
+    pub fn total_supply_at_block(&self, block_number: u64) -> Result<U256, Vec<u8>> {
+        Ok(self._total_supply_at_block(block_number))
+    }
+
     pub fn balance_of(&self, owner: Address) -> Result<U256, Vec<u8>> {
         Ok(self._balance_of(owner))
     }
@@ -106,244 +761,3457 @@ where
         self._transfer(to, amount).map_err(|e| e.encode())
     }
 
+    /// Non-standard: moves tokens like `transfer` but emits no `Transfer`
+    /// event, so indexers will not see it. For internal protocol use only.
+    pub fn transfer_quiet(&mut self, to: Address, amount: U256) -> Result<bool, Vec<u8>> {
+        self._transfer_quiet(to, amount).map_err(|e| e.encode())
+    }
+
     pub fn allowance(&self, owner: Address, spender: Address) -> Result<U256, Vec<u8>> {
         Ok(self._allowance(owner, spender))
     }
 
-    pub fn approve(&mut self, spender: Address, amount: U256) -> Result<bool, Vec<u8>> {
-        self._approve(spender, amount).map_err(|e| e.encode())
+    /// The allowance `owner` has granted each of `spenders`, in order.
+    pub fn allowances_for(
+        &self,
+        owner: Address,
+        spenders: Vec<Address>,
+    ) -> Result<Vec<U256>, Vec<u8>> {
+        Ok(spenders
+            .into_iter()
+            .map(|spender| self._allowance(owner, spender))
+            .collect())
     }
 
-    pub fn transfer_from(
-        &mut self,
-        from: Address,
-        to: Address,
-        amount: U256,
-    ) -> Result<bool, Vec<u8>> {
-        self._transfer_from(from, to, amount)
-            .map_err(|e| e.encode())
+    /// Up to `count` of the most recent changes to `owner`'s allowance for
+    /// `spender`, newest first, as `(amount, blockNumber)` pairs. Empty unless
+    /// `ALLOWANCE_HISTORY_LEN` is set.
+    pub fn last_allowance_changes(
+        &self,
+        owner: Address,
+        spender: Address,
+        count: u64,
+    ) -> Result<Vec<(U256, u64)>, Vec<u8>> {
+        Ok(self._last_allowance_changes(owner, spender, count))
     }
 
-    pub fn permit(
+    pub fn approve(&mut self, spender: Address, amount: U256) -> Result<bool, Vec<u8>> {
+        self._approve(spender, amount).map_err(|e| e.encode())
+    }
+
+    /// Clears an allowance whose expiration has passed, so it stops showing
+    /// up in wallets. Anyone may call this.
+    pub fn sweep_expired_allowance(
         &mut self,
         owner: Address,
         spender: Address,
-        value: U256,
-        deadline: U256,
-        v: u8,
-        r: U256,
-        s: U256,
     ) -> Result<(), Vec<u8>> {
-        self._permit(owner, spender, value, deadline, v, r, s)
+        self._sweep_expired_allowance(owner, spender)
             .map_err(|e| e.encode())
     }
 
-    pub fn transfer_with_permit(
+    /// `approve` with an allowance that lapses to zero after `deadline`, so
+    /// the spender can't sit on it. `deadline` must not have passed, and is
+    /// brought forward to `DEFAULT_ALLOWANCE_TTL` from now if that is sooner.
+    pub fn approve_with_deadline(
         &mut self,
-        to: Address,
+        spender: Address,
+        amount: U256,
+        deadline: u64,
+    ) -> Result<bool, Vec<u8>> {
+        self._approve_with_deadline(spender, amount, deadline)
+            .map_err(|e| e.encode())
+    }
+
+    /// Raises the caller's allowance for `spender` by `added_value`, keeping
+    /// its expiration and spend cap. An expired allowance stays expired;
+    /// `approve` afresh instead.
+    pub fn increase_allowance(
+        &mut self,
+        spender: Address,
+        added_value: U256,
+    ) -> Result<bool, Vec<u8>> {
+        self._increase_allowance(spender, added_value)
+            .map_err(|e| e.encode())
+    }
+
+    /// Lowers the caller's allowance for `spender` by `subtracted_value`,
+    /// keeping its expiration and spend cap.
+    pub fn decrease_allowance(
+        &mut self,
+        spender: Address,
+        subtracted_value: U256,
+    ) -> Result<bool, Vec<u8>> {
+        self._decrease_allowance(spender, subtracted_value)
+            .map_err(|e| e.encode())
+    }
+
+    /// Sets the caller's allowance for `spender` to zero.
+    pub fn revoke(&mut self, spender: Address) -> Result<bool, Vec<u8>> {
+        self._approve(spender, U256::ZERO).map_err(|e| e.encode())
+    }
+
+    /// Sets `owner`'s allowance for `spender` to zero on their signed
+    /// `RevokeApproval`, so a relayer can revoke on their behalf. Shares the
+    /// sequential nonce with `permit`.
+    pub fn revoke_with_sig(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._revoke_with_sig(owner, spender, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    pub fn transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_from(from, to, amount)
+            .map_err(|e| e.encode())
+    }
+
+    /// Whether `spender` could pull `amount` from `owner` right now: the
+    /// allowance and any spend cap cover it, and so does `owner`'s unfrozen
+    /// balance.
+    pub fn can_transfer_from(
+        &self,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<bool, Vec<u8>> {
+        let cap = self.spend_caps.get(owner).get(spender);
+        let spendable = self
+            ._balance_of(owner)
+            .saturating_sub(self.frozen.get(owner));
+        Ok(self._allowance(owner, spender) >= amount
+            && (cap == U256::ZERO || amount <= cap)
+            && spendable >= amount)
+    }
+
+    /// Burns `amount` of `from`'s tokens using the caller's allowance.
+    pub fn burn_from(&mut self, from: Address, amount: U256) -> Result<(), Vec<u8>> {
+        self._burn_from(from, amount).map_err(|e| e.encode())
+    }
+
+    pub fn permit(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit(owner, spender, value, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` taking `r` and `s` packed into a single 64-byte `r ‖ s`, as
+    /// some clients produce them. The ABI has no `bytes64`, so this is routed
+    /// as `bytes` and any other length is rejected. `v` is a full word, so
+    /// EIP-155 values for any chain id are accepted.
+    pub fn permit_packed(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: U256,
+        rs: Bytes,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_packed(owner, spender, value, deadline, v, &rs)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` that reverts if `value` is below the current allowance, unless
+    /// `allow_decrease` is set. Accepts the same signature as `permit`.
+    pub fn permit_strict(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        allow_decrease: bool,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_strict(owner, spender, value, deadline, allow_decrease, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` where the spender is implicitly the caller, as in Uniswap's
+    /// `SelfPermit`. Saves calldata and rules out a spender mismatch for
+    /// routers that permit-then-act.
+    pub fn self_permit(
+        &mut self,
+        owner: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit(owner, msg::sender(), value, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` that adds `added_value` to the current allowance rather than
+    /// overwriting it, keeping its expiration and spend cap. Signed as a
+    /// `PermitIncrease`, so it can't be confused with a standard `Permit`.
+    pub fn permit_increase(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        added_value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_increase(owner, spender, added_value, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` for an allowance that lapses to zero after `expiration`, for
+    /// recurring pulls that shouldn't outlive a subscription. Signed as a
+    /// `PermitWithExpiry`, sharing the sequential nonce with `permit`.
+    pub fn permit_with_expiry(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        expiration: u64,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_with_expiry(owner, spender, value, deadline, expiration, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` that also caps how much `spender` may pull in a single
+    /// `transfer_from`, bounding the damage a buggy spender can do. Signed as
+    /// a `PermitWithCap`, sharing the sequential nonce with `permit`.
+    pub fn permit_with_cap(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        max_spend_per_tx: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_with_cap(owner, spender, value, max_spend_per_tx, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` approving several spenders from one signature, setting
+    /// `spenders[i]`'s allowance to `values[i]`. Signed as a `PermitMulti`,
+    /// consuming a single sequential nonce.
+    pub fn permit_multi(
+        &mut self,
+        owner: Address,
+        spenders: Vec<Address>,
+        values: Vec<U256>,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_multi(owner, spenders, values, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// The account jointly controlled by `threshold` of `owner_set` through
+    /// `permit_multisig`. The set must be strictly ascending. Nobody holds
+    /// its key, so tokens sent there move only by multisig permit.
+    pub fn multisig_account(
+        &self,
+        owner_set: Vec<Address>,
+        threshold: u8,
+    ) -> Result<Address, Vec<u8>> {
+        Ok(multisig_account(&owner_set, threshold))
+    }
+
+    /// `permit` on behalf of the `multisig_account` of `owner_set`, approved
+    /// by at least `threshold` distinct members. `signatures` packs one
+    /// 65-byte `r ‖ s ‖ v` signature per signer, each over the account's
+    /// `Permit`. A member signing twice counts once.
+    pub fn permit_multisig(
+        &mut self,
+        owner_set: Vec<Address>,
+        threshold: u8,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        signatures: Bytes,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_multisig(owner_set, threshold, spender, value, deadline, &signatures)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` that also pays `relayer_fee` of `owner`'s tokens to the
+    /// relayer submitting it. A zero `relayer` pays whoever submits. Signed as
+    /// a `PermitWithRelayerFee`, sharing the sequential nonce with `permit`.
+    pub fn permit_with_relayer_fee(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        relayer: Address,
+        relayer_fee: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_with_relayer_fee(
+            owner,
+            spender,
+            value,
+            relayer,
+            relayer_fee,
+            deadline,
+            v,
+            r,
+            s,
+        )
+        .map_err(|e| e.encode())
+    }
+
+    /// `permit` with a deadline given as a block number rather than a
+    /// timestamp. Signed as a `PermitBlockDeadline`, sharing the sequential
+    /// nonce with `permit`.
+    pub fn permit_block_deadline(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline_block: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_block_deadline(owner, spender, value, deadline_block, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `permit` against a caller-chosen nonce from an unordered bitmap rather
+    /// than the sequential counter, so orders signed with distinct nonces can
+    /// be used or canceled in any order. Signed as an `UnorderedPermit`.
+    pub fn permit_with_nonce(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        nonce: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit_with_nonce(owner, spender, value, deadline, nonce, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// Marks the caller's unordered `nonce` as used, canceling any permit
+    /// signed with it.
+    pub fn invalidate_nonce(&mut self, nonce: U256) -> Result<(), Vec<u8>> {
+        self.use_unordered_nonce(msg::sender(), nonce)
+            .map_err(|e| e.encode())
+    }
+
+    /// The 256-bit word of `owner`'s unordered nonce bitmap at `word_pos`.
+    /// Nonce `n` is bit `n % 256` of word `n / 256`.
+    pub fn nonce_bitmap(&self, owner: Address, word_pos: U256) -> Result<U256, Vec<u8>> {
+        Ok(self.nonce_bitmaps.get(owner).get(word_pos))
+    }
+
+    /// The lowest unordered nonce `owner` hasn't used, i.e. the lowest unset
+    /// bit of their bitmap. Any unused nonce works with `permit_with_nonce`;
+    /// this is just the natural next one. Scans a word per 256 nonces used.
+    pub fn next_unordered_nonce(&self, owner: Address) -> Result<U256, Vec<u8>> {
+        let bitmaps = self.nonce_bitmaps.get(owner);
+        let mut word_pos = U256::ZERO;
+        loop {
+            let word = bitmaps.get(word_pos);
+            if word != U256::MAX {
+                let bit = (!word).trailing_zeros();
+                return Ok((word_pos << 8) | U256::from(bit));
+            }
+            word_pos += U256::from(1);
+        }
+    }
+
+    /// Seconds until a permit with `deadline` expires by this contract's
+    /// clock, or zero once it has.
+    pub fn permit_time_left(&self, deadline: U256) -> Result<U256, Vec<u8>> {
+        Ok(deadline.saturating_sub(U256::from(block::timestamp())))
+    }
+
+    /// The per-transfer cap on `spender`'s pulls from `owner`. Zero means
+    /// uncapped.
+    pub fn spend_cap(&self, owner: Address, spender: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.spend_caps.get(owner).get(spender))
+    }
+
+    /// `permit` with a narrow deadline to save calldata. The deadline is
+    /// widened before hashing, so signatures over the standard `Permit` struct
+    /// are accepted unchanged. The SDK has no `uint48` ABI type, so this is
+    /// routed with a `uint64` deadline.
+    pub fn permit_u48(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: u64,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._permit(owner, spender, value, U256::from(deadline), v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// Applies a permit, then pulls `amount` from `owner` to `to` using the
+    /// caller's allowance. `spender` must therefore be the caller.
+    pub fn transfer_with_permit(
+        &mut self,
+        to: Address,
+        amount: U256,
+
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_with_permit(to, amount, owner, spender, value, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// `transfer_with_permit`, additionally returning the permit nonce it
+    /// consumed for offchain reconciliation.
+    pub fn transfer_with_permit_nonce(
+        &mut self,
+        to: Address,
+        amount: U256,
+
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(bool, U256), Vec<u8>> {
+        let nonce = self.permit_nonce(owner, spender);
+        let ok = self
+            ._transfer_with_permit(to, amount, owner, spender, value, deadline, v, r, s)
+            .map_err(|e| e.encode())?;
+        Ok((ok, nonce))
+    }
+
+    /// Atomically swaps `maker_amount` of this token from `maker` for
+    /// `taker_amount` of `taker_token` from `taker`. The maker signs a `Swap`
+    /// naming the taker and their leg, consuming their permit nonce for this
+    /// contract. The taker signs an EIP-2612 permit on `taker_token` naming
+    /// this contract as spender; if it fails, e.g. because it was front-run,
+    /// an allowance that already covers `taker_amount` is used instead.
+    /// Reverts unless the maker's `taker_token` balance rises by at least
+    /// `taker_amount`.
+    pub fn swap_with_permits(
+        &mut self,
+        maker: Address,
+        maker_amount: U256,
+        maker_deadline: U256,
+        maker_v: u8,
+        maker_r: U256,
+        maker_s: U256,
+
+        taker: Address,
+        taker_token: Address,
+        taker_amount: U256,
+        taker_deadline: U256,
+        taker_v: u8,
+        taker_r: U256,
+        taker_s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._swap_with_permits(
+            maker,
+            maker_amount,
+            maker_deadline,
+            maker_v,
+            maker_r,
+            maker_s,
+            taker,
+            taker_token,
+            taker_amount,
+            taker_deadline,
+            taker_v,
+            taker_r,
+            taker_s,
+        )
+        .map_err(|e| e.encode())
+    }
+
+    /// Relayer-oriented form of `transfer_with_permit`, taking the permit
+    /// first. The caller submits the owner's permit naming itself as
+    /// `spender`, then moves `amount` of the owner's tokens to an arbitrary
+    /// `to` in the same transaction.
+    pub fn permit_and_transfer_from(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+
+        to: Address,
+        amount: U256,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_with_permit(to, amount, owner, spender, value, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// EIP-3009 `transferWithAuthorization`. Anyone may submit `from`'s
+    /// signed authorization to move `value` to `to`.
+    pub fn transfer_with_authorization(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+        valid_after: U256,
+        valid_before: U256,
+        nonce: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        let auth = TransferWithAuthorization {
+            from,
+            to,
+            value,
+            validAfter: valid_after,
+            validBefore: valid_before,
+            nonce: *nonce,
+        };
+        self._use_authorization(
+            &auth,
+            from,
+            to,
+            value,
+            valid_after,
+            valid_before,
+            nonce,
+            v,
+            r,
+            s,
+        )
+        .map_err(|e| e.encode())
+    }
+
+    /// EIP-3009 `receiveWithAuthorization`. As `transfer_with_authorization`,
+    /// but only the payee may submit it, preventing front-running of calls
+    /// that wrap the transfer.
+    pub fn receive_with_authorization(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+        valid_after: U256,
+        valid_before: U256,
+        nonce: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        if to != msg::sender() {
+            return Err(Erc20Errors::Unauthorized(Default::default()).encode());
+        }
+        let auth = ReceiveWithAuthorization {
+            from,
+            to,
+            value,
+            validAfter: valid_after,
+            validBefore: valid_before,
+            nonce: *nonce,
+        };
+        self._use_authorization(
+            &auth,
+            from,
+            to,
+            value,
+            valid_after,
+            valid_before,
+            nonce,
+            v,
+            r,
+            s,
+        )
+        .map_err(|e| e.encode())
+    }
+
+    /// EIP-3009 `authorizationState`. Whether `nonce` has been used or
+    /// canceled by `authorizer`.
+    pub fn authorization_state(
+        &self,
+        authorizer: Address,
+        nonce: FixedBytes<32>,
+    ) -> Result<bool, Vec<u8>> {
+        Ok(self.authorization_states.get(authorizer).get(nonce))
+    }
+
+    /// EIP-3009 `cancelAuthorization`. Marks an unused nonce as used.
+    pub fn cancel_authorization(
+        &mut self,
+        authorizer: Address,
+        nonce: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._cancel_authorization(authorizer, nonce, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// Transfers to many recipients at once. The sender is debited for each
+    /// recipient in turn, and the whole batch reverts if any of them can't be
+    /// covered.
+    pub fn transfer_batch(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_batch(recipients, amounts)
+            .map_err(|e| e.encode())
+    }
+
+    /// `transfer_batch` that first sorts the recipients by address and
+    /// merges duplicates, summing their amounts. Transfers are applied, and
+    /// `Transfer` events emitted, once per unique recipient in address order
+    /// rather than in the order given.
+    pub fn transfer_batch_optimized(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_batch_optimized(recipients, amounts)
+            .map_err(|e| e.encode())
+    }
+
+    /// Transfers to `to`, which must accept via `onTransferReceived` if it is
+    /// a contract. If it doesn't, the tokens it received are held by this
+    /// contract for it to `withdraw` instead of reverting.
+    pub fn transfer_or_credit(&mut self, to: Address, amount: U256) -> Result<bool, Vec<u8>> {
+        self._transfer_or_credit(to, amount).map_err(|e| e.encode())
+    }
+
+    /// Tokens held for `account` by `transfer_or_credit`. Credits are kept
+    /// as shares, so under `REBASING` they rebase with everyone's balance.
+    pub fn credits_of(&self, account: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.amount_for(self.credits.get(account)))
+    }
+
+    /// Pays out the caller's credits, returning the amount withdrawn.
+    pub fn withdraw(&mut self) -> Result<U256, Vec<u8>> {
+        self._withdraw().map_err(|e| e.encode())
+    }
+
+    /// ERC-1363 `transferAndCall`. If `to` is a contract, it must accept the
+    /// transfer via `onTransferReceived`, which can't call back into this
+    /// token.
+    pub fn transfer_and_call(
+        &mut self,
+        to: Address,
+        amount: U256,
+        data: Bytes,
+    ) -> Result<bool, Vec<u8>> {
+        self._transfer_and_call(to, amount, data.0)
+            .map_err(|e| e.encode())
+    }
+
+    /// ERC-777-style: lets `operator` move any amount of the caller's tokens.
+    pub fn authorize_operator(&mut self, operator: Address) -> Result<(), Vec<u8>> {
+        self._authorize_operator(operator).map_err(|e| e.encode())
+    }
+
+    pub fn revoke_operator(&mut self, operator: Address) -> Result<(), Vec<u8>> {
+        self._revoke_operator(operator).map_err(|e| e.encode())
+    }
+
+    /// Whether `operator` may move `holder`'s tokens. Holders are always
+    /// their own operator.
+    pub fn is_operator_for(&self, operator: Address, holder: Address) -> Result<bool, Vec<u8>> {
+        Ok(self._is_operator_for(operator, holder))
+    }
+
+    /// Moves `from`'s tokens as an authorized operator, bypassing allowances.
+    /// If `to` is a contract, it must accept the transfer via
+    /// `onTransferReceived`.
+    pub fn operator_transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        amount: U256,
+        data: Bytes,
+    ) -> Result<(), Vec<u8>> {
+        self._operator_transfer(from, to, amount, data.0)
+            .map_err(|e| e.encode())
+    }
+
+    /// ERC-1363 `approveAndCall`. If `spender` is a contract, it must accept
+    /// the approval via `onApprovalReceived`. The SDK reverts any call back
+    /// into this token from the hook, so the spender can't `transferFrom`
+    /// there; it must pull the tokens in a later call.
+    pub fn approve_and_call(
+        &mut self,
+        spender: Address,
+        amount: U256,
+        data: Bytes,
+    ) -> Result<bool, Vec<u8>> {
+        self._approve_and_call(spender, amount, data.0)
+            .map_err(|e| e.encode())
+    }
+
+    /// Raw units held by `owner`. Equal to `balance_of` unless `REBASING` is
+    /// set.
+    pub fn shares_of(&self, owner: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.balances.get(owner))
+    }
+
+    pub fn total_shares(&self) -> Result<U256, Vec<u8>> {
+        Ok(self.total_supply.get())
+    }
+
+    pub fn rebase_index(&self) -> Result<U256, Vec<u8>> {
+        Ok(self.current_index())
+    }
+
+    /// Sets the rebase index, scaling every balance by
+    /// `new_index / rebase_index()`. Only the owner may call this, and only
+    /// on `REBASING` tokens.
+    pub fn rebase(&mut self, new_index: U256) -> Result<(), Vec<u8>> {
+        self._rebase(new_index).map_err(|e| e.encode())
+    }
+
+    pub fn fee_recipient(&self) -> Result<Address, Vec<u8>> {
+        Ok(self.fee_recipient.get())
+    }
+
+    pub fn set_fee_recipient(&mut self, recipient: Address) -> Result<(), Vec<u8>> {
+        self._set_fee_recipient(recipient).map_err(|e| e.encode())
+    }
+
+    /// Opens migration mode for `MIGRATION_WINDOW_BLOCKS` blocks. It can only
+    /// ever be enabled once. Only the owner may call this.
+    pub fn enable_migration_mode(&mut self) -> Result<(), Vec<u8>> {
+        self._enable_migration_mode().map_err(|e| e.encode())
+    }
+
+    /// Whether migration mode is currently open.
+    pub fn migration_mode(&self) -> Result<bool, Vec<u8>> {
+        Ok(self.in_migration())
+    }
+
+    /// Dangerous: overwrites `account`'s balance, minting or burning the
+    /// difference, for moving balances to a new implementation. Skips hooks,
+    /// fees and freezes. Only the owner may call this, and only while
+    /// migration mode is open.
+    pub fn emergency_set_balance(&mut self, account: Address, amount: U256) -> Result<(), Vec<u8>> {
+        self._emergency_set_balance(account, amount)
+            .map_err(|e| e.encode())
+    }
+
+    /// Registers the caller's own domain salt under `PER_OWNER_SALT`,
+    /// invalidating their outstanding signatures. Zero clears it.
+    pub fn set_owner_salt(&mut self, salt: FixedBytes<32>) -> Result<(), Vec<u8>> {
+        self.owner_salts.insert(msg::sender(), salt);
+        Ok(())
+    }
+
+    /// `owner`'s registered domain salt, or zero if none.
+    pub fn owner_salt(&self, owner: Address) -> Result<FixedBytes<32>, Vec<u8>> {
+        Ok(self.owner_salts.get(owner))
+    }
+
+    /// Replaces the EIP-712 domain salt, overriding `DomainInfo::SALT`. This
+    /// invalidates every outstanding signature, e.g. after a suspected
+    /// compromise in signing tooling. Only the owner may call this.
+    pub fn set_domain_salt(&mut self, salt: FixedBytes<32>) -> Result<(), Vec<u8>> {
+        self._set_domain_salt(salt).map_err(|e| e.encode())
+    }
+
+    /// The number of spenders `owner` has a nonzero stored allowance for.
+    /// Expired allowances count until they are swept or overwritten.
+    pub fn approval_count(&self, owner: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.approval_counts.get(owner))
+    }
+
+    /// The number of accounts holding a nonzero balance.
+    pub fn holder_count(&self) -> Result<U256, Vec<u8>> {
+        Ok(self.holder_count.get())
+    }
+
+    /// The portion of `addr`'s balance that may not be transferred.
+    pub fn frozen_of(&self, addr: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.frozen.get(addr))
+    }
+
+    /// Freezes a further `amount` of `addr`'s balance. Owner only.
+    pub fn freeze(&mut self, addr: Address, amount: U256) -> Result<(), Vec<u8>> {
+        self._freeze(addr, amount).map_err(|e| e.encode())
+    }
+
+    /// Releases `amount` of `addr`'s frozen balance. Owner only.
+    pub fn unfreeze(&mut self, addr: Address, amount: U256) -> Result<(), Vec<u8>> {
+        self._unfreeze(addr, amount).map_err(|e| e.encode())
+    }
+
+    /// Applies `owner`'s EIP-2612 permit on the `COLLATERAL` token for this
+    /// contract, pulls `amount` of collateral from `owner`, and mints them the
+    /// same amount of this token. A failed permit is tolerated if the
+    /// allowance already covers `amount`, e.g. because it was front-run.
+    pub fn mint_with_permit(
+        &mut self,
+        owner: Address,
+        amount: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._mint_with_permit(owner, amount, deadline, v, r, s)
+            .map_err(|e| e.encode())
+    }
+
+    /// Mints up to `amount` to `to`, clamping rather than reverting, and
+    /// returns the amount minted. Only the owner may call this.
+    pub fn mint_saturating(&mut self, to: Address, amount: U256) -> Result<U256, Vec<u8>> {
+        self._owner_mint_saturating(to, amount)
+            .map_err(|e| e.encode())
+    }
+
+    /// Burns up to `amount` from `from`, clamping to their balance, and
+    /// returns the amount burned. Only the owner may call this.
+    pub fn slash(&mut self, from: Address, amount: U256) -> Result<U256, Vec<u8>> {
+        self._slash(from, amount).map_err(|e| e.encode())
+    }
+
+    /// Sends foreign tokens mistakenly transferred to this contract to `to`.
+    /// Only the owner may call this, and this token can't be rescued.
+    pub fn rescue_tokens(
+        &mut self,
+        token: Address,
+        to: Address,
+        amount: U256,
+    ) -> Result<(), Vec<u8>> {
+        self._rescue_tokens(token, to, amount)
+            .map_err(|e| e.encode())
+    }
+
+    /// The nonce a permit from `owner` to `spender` must sign over. This is
+    /// the owner's shared nonce unless `PER_SPENDER_NONCES` is set.
+    pub fn nonce_for(&self, owner: Address, spender: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.permit_nonce(owner, spender))
+    }
+
+    /// Sends any ETH held by this contract to `to`, returning the amount.
+    /// Only the owner may call this.
+    pub fn sweep_eth(&mut self, to: Address) -> Result<U256, Vec<u8>> {
+        self._sweep_eth(to).map_err(|e| e.encode())
+    }
+
+    /// The EIP-2612 permit nonce of `owner`. Under `PER_SPENDER_NONCES`,
+    /// permits use `nonce_for` instead.
+    pub fn nonces(&self, owner: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.nonce_of(owner))
+    }
+
+    /// The nonce `owner` should sign into their next sequential permit to
+    /// `spender`. This equals `nonces` unless `PER_SPENDER_NONCES` is set.
+    /// Unordered nonces used by `permit_with_nonce` are tracked separately;
+    /// `next_unordered_nonce` returns the lowest unused one.
+    pub fn next_nonce(&self, owner: Address, spender: Address) -> Result<U256, Vec<u8>> {
+        Ok(self.permit_nonce(owner, spender))
+    }
+
+    /// Runs each encoded call against this token's methods in order,
+    /// returning their encoded results. Reverts with the first failing
+    /// call's revert data. Intended for batching views; state-changing calls
+    /// also work and run with the caller as `msg::sender()`.
+    ///
+    /// Calls are routed to this type's own methods, not through the contract
+    /// that inherits it. They therefore bypass any custom entrypoint, so
+    /// selectors it hides or aliases are treated as plain inherited ones,
+    /// and methods it overrides (e.g. `supports_interface`) run this type's
+    /// version.
+    pub fn multicall<S>(storage: &mut S, calls: Vec<Bytes>) -> Result<Vec<Bytes>, Vec<u8>>
+    where
+        S: TopLevelStorage + BorrowMut<Self>,
+    {
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            if call.len() < 4 {
+                return Err(unknown_selector(&call));
+            }
+            let selector = u32::from_be_bytes(call[..4].try_into().unwrap());
+            match <Self as Router<S>>::route(storage, selector, &call[4..]) {
+                Some(result) => results.push(Bytes(result?)),
+                None => return Err(unknown_selector(&call)),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Queues an encoded owner-only call for `execute` after
+    /// `TIMELOCK_DELAY`, returning its id. Only the owner may call this.
+    pub fn queue(&mut self, data: Bytes) -> Result<FixedBytes<32>, Vec<u8>> {
+        self._queue(&data).map_err(|e| e.encode())
+    }
+
+    /// Drops a queued call. Only the owner may call this.
+    pub fn cancel(&mut self, data: Bytes) -> Result<(), Vec<u8>> {
+        self._cancel(&data).map_err(|e| e.encode())
+    }
+
+    /// Runs a queued call whose delay has passed, returning its encoded
+    /// result. Only the owner may call this. The call is routed as in
+    /// `multicall`.
+    pub fn execute<S>(storage: &mut S, data: Bytes) -> Result<Bytes, Vec<u8>>
+    where
+        S: TopLevelStorage + BorrowMut<Self>,
+    {
+        storage
+            .borrow_mut()
+            .begin_execute(&data)
+            .map_err(|e| e.encode())?;
+
+        if data.len() < 4 {
+            return Err(unknown_selector(&data));
+        }
+        let selector = u32::from_be_bytes(data[..4].try_into().unwrap());
+        let result = match <Self as Router<S>>::route(storage, selector, &data[4..]) {
+            Some(result) => Bytes(result?),
+            None => return Err(unknown_selector(&data)),
+        };
+
+        storage.borrow_mut().timelock_executing.set(false);
+        Ok(result)
+    }
+
+    /// When the queued call with `id` may be executed, or zero if none is
+    /// queued.
+    pub fn timelock_eta(&self, id: FixedBytes<32>) -> Result<u64, Vec<u8>> {
+        Ok(self.timelock_etas.get(id).to::<u64>())
+    }
+
+    /// `(balance, nonce)` of `owner` in one call, for permit-signing UIs. The
+    /// nonce is the one a permit to `spender` must sign over.
+    pub fn account_state(&self, owner: Address, spender: Address) -> Result<(U256, U256), Vec<u8>> {
+        Ok((self._balance_of(owner), self.permit_nonce(owner, spender)))
+    }
+
+    /// The EIP-712 signing hash of a `Permit`, for comparing against offchain
+    /// tooling. Uses the owner's current nonce, so the result changes after
+    /// each permit they use.
+    pub fn permit_hash(
+        &self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+    ) -> Result<FixedBytes<32>, Vec<u8>> {
+        let permit = self.build_permit(owner, spender, value, deadline);
+        Ok(self.view_signing_hash(owner, &permit))
+    }
+
+    /// Recovers the signer of a permit over the owner's current nonce without
+    /// applying it, so relayers can validate before submitting.
+    pub fn recover_permit_signer(
+        &self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<Address, Vec<u8>> {
+        let v = normalize_v(v).ok_or(Erc20Errors::InvalidPermit(Default::default()).encode())?;
+        let permit = self.build_permit(owner, spender, value, deadline);
+        ecrecover(
+            T::ECRECOVER,
+            self.view_signing_hash(owner, &permit),
+            v,
+            r,
+            s,
+        )
+        .map_err(|_| Erc20Errors::InvalidPermit(Default::default()).encode())
+    }
+
+    /// Whether `permit` would succeed with these arguments right now, without
+    /// applying it. A cheap pre-flight for relayers.
+    pub fn can_permit(
+        &self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Result<bool, Vec<u8>> {
+        if owner == Address::ZERO
+            || spender == Address::ZERO
+            || self.check_deadline(deadline).is_err()
+        {
+            return Ok(false);
+        }
+        let Some(v) = normalize_v(v) else {
+            return Ok(false);
+        };
+        let permit = self.build_permit(owner, spender, value, deadline);
+        let hash = self.view_signing_hash(owner, &permit);
+        Ok(ecrecover(T::ECRECOVER, hash, v, r, s) == Ok(owner))
+    }
+
+    /// Whether `expected_signer` signed the typed data with `hashStruct`
+    /// `struct_hash` under this contract's EIP-712 domain. Generalizes permit
+    /// verification for composed flows.
+    pub fn verify_typed_data(
+        &self,
+        struct_hash: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+        expected_signer: Address,
+    ) -> Result<bool, Vec<u8>> {
+        let Some(v) = normalize_v(v) else {
+            return Ok(false);
+        };
+        let separator = self
+            .owner_domain_separator(chainid(), expected_signer)
+            .unwrap_or_else(|| self._domain_separator());
+        let hash = eip712_digest_of(separator, struct_hash);
+        Ok(expected_signer != Address::ZERO
+            && ecrecover(T::ECRECOVER, hash, v, r, s) == Ok(expected_signer))
+    }
+
+    /// The EIP-712 domain version, for offchain signature construction.
+    pub fn version(&self) -> Result<String, Vec<u8>> {
+        Ok(T::VERSION.unwrap_or("1").to_owned())
+    }
+
+    /// The EIP-712 domain separator. Routed as `domainSeparator()`, as the
+    /// SDK camel-cases method names; the sample contract's entrypoint also
+    /// routes the canonical `DOMAIN_SEPARATOR()` here.
+    pub fn domain_separator(&self) -> Result<FixedBytes<32>, Vec<u8>> {
+        Ok(self._domain_separator())
+    }
+
+    /// The EIP-712 type hash of `Permit`, i.e. `PERMIT_TYPEHASH`. Computed
+    /// from the struct's type string so it can't drift from the signed type.
+    pub fn permit_typehash(&self) -> Result<FixedBytes<32>, Vec<u8>> {
+        Ok(permit_typehash())
+    }
+
+    /// The chain id the EIP-712 domain is currently built with, so frontends
+    /// can check their RPC before asking for a signature.
+    pub fn domain_chain_id(&self) -> Result<U256, Vec<u8>> {
+        Ok(U256::from(chainid()))
+    }
+
+    /// The EIP-712 domain's name, version, chain id and verifying contract.
+    /// Unset names and versions are returned as empty strings.
+    pub fn domain_fields(&self) -> Result<(String, String, U256, Address), Vec<u8>> {
+        let domain = self.get_domain(chainid());
+        Ok((
+            domain.name.unwrap_or_default().into_owned(),
+            domain.version.unwrap_or_default().into_owned(),
+            domain.chain_id.unwrap_or_default(),
+            domain.verifying_contract.unwrap_or_default(),
+        ))
+    }
+
+    /// ERC-165: true for the ERC-165 and ERC-20 interface ids. Not ERC-2612,
+    /// whose `permit` takes `bytes32` signature words where this one takes
+    /// `uint256`, and which requires `DOMAIN_SEPARATOR()`; a contract that
+    /// routes those selectors may report it.
+    pub fn supports_interface(&self, interface_id: FixedBytes<4>) -> Result<bool, Vec<u8>> {
+        Ok(is_supported_interface(interface_id.0))
+    }
+}
+
+impl<T, U> Erc20Permit<T, U>
+where
+    T: DomainInfo,
+    U: Erc20Details + TransferHook + TransferFee,
+{
+    /// Mints exactly `amount` to `to`, erroring with `BalanceOverflow` if the
+    /// balance or total supply can't hold it.
+    pub fn _mint(&mut self, to: Address, amount: U256) -> Erc20Result<()> {
+        self.mint_with(to, amount, false).map(|_| ())
+    }
+
+    /// Mints up to `amount` to `to`, clamping at the maximum balance or total
+    /// supply rather than erroring, and returns the amount actually minted.
+    pub fn _mint_saturating(&mut self, to: Address, amount: U256) -> Erc20Result<U256> {
+        self.mint_with(to, amount, true)
+    }
+
+    fn mint_with(&mut self, to: Address, amount: U256, saturating: bool) -> Erc20Result<U256> {
+        U::before_token_transfer(self, Address::ZERO, to, amount)?;
+        self.consume_mint_budget(amount)?;
+
+        let minted_shares = self.mint_shares(to, amount, saturating)?;
+        let minted = self.amount_for(minted_shares);
+
+        evm::log(Erc20::Transfer {
+            from: Address::ZERO,
+            to,
+            amount: minted,
+        });
+        #[cfg(feature = "mint-burn-events")]
+        evm::log(Erc20::Mint { to, amount: minted });
+        U::after_token_transfer(self, Address::ZERO, to, minted)?;
+        Ok(minted)
+    }
+
+    /// Counts `amount` against `MAX_MINT_PER_BLOCK`, if set.
+    fn consume_mint_budget(&mut self, amount: U256) -> Erc20Result<()> {
+        let Some(limit) = U::MAX_MINT_PER_BLOCK else {
+            return Ok(());
+        };
+
+        let block = U64::from(block::number());
+        let already = if self.mint_block.get() == block {
+            self.minted_in_block.get()
+        } else {
+            U256::ZERO
+        };
+        let minted = already
+            .checked_add(amount)
+            .filter(|minted| *minted <= limit)
+            .ok_or(Erc20::Erc20Errors::MintRateExceeded(Default::default()))?;
+
+        self.mint_block.set(block);
+        self.minted_in_block.set(minted);
+        Ok(())
+    }
+
+    /// Burns `amount` from `from`, erroring if their balance is insufficient.
+    pub fn _burn(&mut self, from: Address, amount: U256) -> Erc20Result<()> {
+        U::before_token_transfer(self, from, Address::ZERO, amount)?;
+        let total = self.total_supply.get();
+
+        let shares = self.debit(from, amount)?;
+        self.total_supply.set(total - shares);
+        self.checkpoint_supply();
+
+        evm::log(Erc20::Transfer {
+            from,
+            to: Address::ZERO,
+            amount,
+        });
+        #[cfg(feature = "mint-burn-events")]
+        evm::log(Erc20::Burn { from, amount });
+        U::after_token_transfer(self, from, Address::ZERO, amount)
+    }
+
+    /// Burns up to `amount` from `from`, clamping to their balance rather than
+    /// erroring, and returns the amount actually burned. For slashing-style
+    /// uses where a partial burn is acceptable.
+    pub fn _burn_saturating(&mut self, from: Address, amount: U256) -> Erc20Result<U256> {
+        U::before_token_transfer(self, from, Address::ZERO, amount)?;
+        let total = self.total_supply.get();
+
+        let burned_shares = self.saturating_debit(from, amount)?;
+        self.total_supply.set(total - burned_shares);
+        self.checkpoint_supply();
+        let burned = self.amount_for(burned_shares);
+
+        evm::log(Erc20::Transfer {
+            from,
+            to: Address::ZERO,
+            amount: burned,
+        });
+        #[cfg(feature = "mint-burn-events")]
+        evm::log(Erc20::Burn {
+            from,
+            amount: burned,
+        });
+        U::after_token_transfer(self, from, Address::ZERO, burned)?;
+        Ok(burned)
+    }
+
+    pub fn _init(
+        &mut self,
+        initial_holder: Address,
+        initial_supply: U256,
+        owner: Address,
+    ) -> Erc20Result<()> {
+        if self.initialized.get() {
+            return Err(Erc20::Erc20Errors::AlreadyInitialized(Default::default()));
+        }
+        self.initialized.set(true);
+        self.set_owner(owner);
+        // Warm the separator cache so the first permit reads one slot rather
+        // than hashing the domain.
+        self.refresh_domain_separator(chainid());
+        self._mint(initial_holder, initial_supply)
+    }
+
+    /// Runs `f` while holding the reentrancy lock, returning an error if the
+    /// lock is already held. Wrap any path that makes a mutating call into
+    /// another contract. Static calls (e.g. to ECRECOVER) cannot re-enter and
+    /// don't need it. Without stylus-sdk's `reentrant` feature, which this
+    /// crate doesn't enable, the SDK already reverts reentrant calls; the lock
+    /// keeps these paths safe if the feature is turned on.
+    pub fn non_reentrant<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Erc20Result<R>,
+    ) -> Erc20Result<R> {
+        if self.locked.get() {
+            return Err(Erc20::Erc20Errors::Reentrancy(Default::default()));
+        }
+        self.locked.set(true);
+        let res = f(self);
+        self.locked.set(false);
+        res
+    }
+
+    /// The domain name, falling back to the token name if `NAME_FALLBACK` is
+    /// set.
+    fn domain_name(&self) -> Option<Cow<'static, str>> {
+        T::domain_name(self).or_else(|| T::NAME_FALLBACK.then_some(Cow::Borrowed(U::NAME)))
+    }
+
+    fn get_domain(&self, chain_id: u64) -> Eip712Domain {
+        Eip712Domain {
+            name: self.domain_name(),
+            version: T::VERSION.map(Cow::Borrowed),
+            chain_id: Some(U256::from(chain_id)),
+            verifying_contract: Some(contract::address()),
+            salt: if self.domain_salt_set.get() {
+                Some(self.domain_salt.get())
+            } else {
+                T::SALT
+            },
+        }
+    }
+
+    /// Returns the cached domain separator if it was computed for the current
+    /// chain id, and computes it otherwise. The cache is filled in `_init`, so
+    /// it only misses after a fork or a salt change. A hit still reads two
+    /// slots, as the separator fills one and the chain id it was computed
+    /// for can't be packed alongside it.
+    fn _domain_separator(&self) -> FixedBytes<32> {
+        let chain_id = chainid();
+        if self.cached_chain_id.get() == U64::from(chain_id) {
+            return self.cached_domain_separator.get();
+        }
+        self.get_domain(chain_id).separator()
+    }
+
+    /// Returns the domain separator, refreshing the cache if the chain id has
+    /// changed since it was last computed (e.g. after a fork). Takes the live
+    /// chain id so callers can share a single host call.
+    fn refresh_domain_separator(&mut self, chain_id: u64) -> FixedBytes<32> {
+        if self.cached_chain_id.get() == U64::from(chain_id) {
+            return self.cached_domain_separator.get();
+        }
+
+        let separator = self.get_domain(chain_id).separator();
+        self.cached_domain_separator.set(separator);
+        self.cached_chain_id.set(U64::from(chain_id));
+        separator
+    }
+
+    /// The domain separator for signatures by `owner`, if they have a salt
+    /// of their own under `PER_OWNER_SALT`. These are not cached.
+    fn owner_domain_separator(&self, chain_id: u64, owner: Address) -> Option<FixedBytes<32>> {
+        if !T::PER_OWNER_SALT {
+            return None;
+        }
+        let owner_salt = self.owner_salts.get(owner);
+        if owner_salt == FixedBytes::ZERO {
+            return None;
+        }
+
+        let mut domain = self.get_domain(chain_id);
+        let mut salts = [0u8; 64];
+        salts[..32].copy_from_slice(&domain.salt.unwrap_or_default()[..]);
+        salts[32..].copy_from_slice(&owner_salt[..]);
+        domain.salt = Some(keccak256(salts));
+        Some(domain.separator())
+    }
+
+    /// Computes the EIP-712 signing hash of a struct signed by `owner`
+    /// without refreshing the domain separator cache. For views.
+    fn view_signing_hash<S: SolStruct>(&self, owner: Address, data: &S) -> FixedBytes<32> {
+        let separator = self
+            .owner_domain_separator(chainid(), owner)
+            .unwrap_or_else(|| self._domain_separator());
+        eip712_digest(separator, data)
+    }
+
+    /// Builds the `Permit` struct an owner signs, using their current nonce.
+    fn build_permit(
+        &self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+    ) -> Permit {
+        Permit {
+            owner,
+            spender,
+            value,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+        }
+    }
+
+    /// The rebase index, which is `REBASE_PRECISION` until the first rebase.
+    fn current_index(&self) -> U256 {
+        let index = self.rebase_index.get();
+        if index == U256::ZERO {
+            return REBASE_PRECISION;
+        }
+        index
+    }
+
+    /// Converts a token amount to the units stored in `balances`. This is the
+    /// identity unless `REBASING` is set, and rounds down otherwise, so
+    /// credits never exceed what was paid in.
+    fn shares_for(&self, amount: U256) -> U256 {
+        if !U::REBASING {
+            return amount;
+        }
+        mul_div_floor(amount, REBASE_PRECISION, self.current_index())
+    }
+
+    /// As `shares_for`, but `None` if the shares don't fit in a `U256`, which
+    /// can happen while the index is below `REBASE_PRECISION`.
+    fn checked_shares_for(&self, amount: U256) -> Option<U256> {
+        if !U::REBASING {
+            return Some(amount);
+        }
+        checked_mul_div_floor(amount, REBASE_PRECISION, self.current_index())
+    }
+
+    /// The most shares whose token amount still fits in a `U256`.
+    fn max_shares(&self) -> U256 {
+        self.checked_shares_for(U256::MAX).unwrap_or(U256::MAX)
+    }
+
+    /// As `shares_for`, but rounds up, so debits never fall short of the
+    /// amount paid out.
+    fn shares_for_debit(&self, amount: U256) -> U256 {
+        if !U::REBASING {
+            return amount;
+        }
+        mul_div_ceil(amount, REBASE_PRECISION, self.current_index())
+    }
+
+    /// Converts units stored in `balances` to a token amount, rounding down.
+    fn amount_for(&self, shares: U256) -> U256 {
+        if !U::REBASING {
+            return shares;
+        }
+        mul_div_floor(shares, self.current_index(), REBASE_PRECISION)
+    }
+
+    /// Debits an account with the given amount, saturating the balance, and
+    /// returning the shares actually debited.
+    fn saturating_debit(&mut self, addr: Address, amount: U256) -> Erc20Result<U256> {
+        let amount = self.shares_for_debit(amount);
+        let balance = self.balances.get(addr);
+
+        let new_bal = balance.saturating_sub(amount);
+        let burned = balance - new_bal;
+
+        self.set_balance(addr, balance, new_bal);
+
+        Ok(burned)
+    }
+
+    /// Debits an account with the given amount, returning an error if the
+    /// balance is insufficient or the debit would dip into frozen funds.
+    /// Returns the shares debited, which callers crediting the amount
+    /// elsewhere must move exactly so no shares are lost to rounding.
+    fn debit(&mut self, addr: Address, amount: U256) -> Erc20Result<U256> {
+        let shares = self.shares_for_debit(amount);
+        let frozen = self.shares_for_debit(self.frozen.get(addr));
+        let balance = self.balances.get(addr);
+
+        let new_bal = balance
+            .checked_sub(shares)
+            .ok_or(Erc20::Erc20Errors::InsufficientBalance(Default::default()))?;
+        if new_bal < frozen {
+            return Err(Erc20::Erc20Errors::BalanceFrozen(Default::default()));
+        }
+        self.set_balance(addr, balance, new_bal);
+        Ok(shares)
+    }
+
+    /// Credits `amount` of new supply to an account, returning the shares
+    /// minted. If its balance or the total supply would overflow, this errors
+    /// or, if `saturating` is set, clamps in share space.
+    fn mint_shares(&mut self, addr: Address, amount: U256, saturating: bool) -> Erc20Result<U256> {
+        let total = self.total_supply.get();
+        let balance = self.balances.get(addr);
+
+        // Bound the supply so that it still converts to a token amount.
+        let room = self
+            .max_shares()
+            .saturating_sub(total)
+            .min(U256::MAX - balance);
+        let minted = match self.checked_shares_for(amount) {
+            Some(shares) if shares <= room => shares,
+            _ if saturating => room,
+            _ => return Err(Erc20::Erc20Errors::BalanceOverflow(Default::default())),
+        };
+        self.set_balance(addr, balance, balance + minted);
+        self.total_supply.set(total + minted);
+        self.checkpoint_supply();
+
+        Ok(minted)
+    }
+
+    /// Credits an account with an exact number of shares, returning an error
+    /// if the balance would overflow.
+    fn credit_shares(&mut self, addr: Address, shares: U256) -> Erc20Result<()> {
+        let balance = self.balances.get(addr);
+
+        let new_bal = balance
+            .checked_add(shares)
+            .ok_or(Erc20::Erc20Errors::BalanceOverflow(Default::default()))?;
+        self.set_balance(addr, balance, new_bal);
+        Ok(())
+    }
+
+    /// Writes a balance, counting accounts entering or leaving the set of
+    /// holders.
+    fn set_balance(&mut self, addr: Address, old: U256, new: U256) {
+        self.balances.insert(addr, new);
+        if old == U256::ZERO && new != U256::ZERO {
+            let count = self.holder_count.get();
+            self.holder_count.set(count + U256::from(1));
+        } else if old != U256::ZERO && new == U256::ZERO {
+            let count = self.holder_count.get();
+            self.holder_count.set(count - U256::from(1));
+        }
+    }
+
+    fn move_tokens(&mut self, from: Address, to: Address, amount: U256) -> Erc20Result<()> {
+        self.move_tokens_with(from, to, amount, true)
+    }
+
+    /// Moves tokens, emitting `Transfer` events only if `emit` is set.
+    fn move_tokens_with(
+        &mut self,
+        from: Address,
+        to: Address,
+        amount: U256,
+        emit: bool,
+    ) -> Erc20Result<()> {
+        U::before_token_transfer(self, from, to, amount)?;
+
+        // Zero-value and self-transfers leave balances unchanged, so skip the
+        // writes.
+        if amount == U256::ZERO {
+            if emit {
+                evm::log(Erc20::Transfer { from, to, amount });
+            }
+            return U::after_token_transfer(self, from, to, amount);
+        }
+        if from == to {
+            if self.balances.get(from) < self.shares_for_debit(amount) {
+                return Err(Erc20::Erc20Errors::InsufficientBalance(Default::default()));
+            }
+            if emit {
+                evm::log(Erc20::Transfer { from, to, amount });
+            }
+            return U::after_token_transfer(self, from, to, amount);
+        }
+
+        let shares = self.debit(from, amount)?;
+        self.deliver(from, to, amount, shares, emit)?;
+        U::after_token_transfer(self, from, to, amount)
+    }
+
+    /// Credits an already-debited amount, worth `shares`, to `to`, routing
+    /// the transfer fee (if any) to the fee recipient. Exactly `shares` are
+    /// credited across the legs. Emits a `Transfer` for each leg if `emit` is
+    /// set.
+    fn deliver(
+        &mut self,
+        from: Address,
+        to: Address,
+        amount: U256,
+        shares: U256,
+        emit: bool,
+    ) -> Erc20Result<()> {
+        let fee_recipient = self.fee_recipient.get();
+        let fee = if U::TRANSFER_FEE_BPS == 0 || fee_recipient == Address::ZERO {
+            U256::ZERO
+        } else {
+            mul_div_floor(amount, U256::from(U::TRANSFER_FEE_BPS), U256::from(10_000))
+        };
+        let net = amount.saturating_sub(fee);
+        if net == U256::ZERO && amount != U256::ZERO {
+            return Err(Erc20::Erc20Errors::TransferTooSmall(Default::default()));
+        }
+
+        let fee_shares = self.shares_for(fee);
+        self.credit_shares(to, shares - fee_shares)?;
+        if emit {
+            evm::log(Erc20::Transfer {
+                from,
+                to,
+                amount: net,
+            });
+        }
+
+        if fee != U256::ZERO {
+            self.credit_shares(fee_recipient, fee_shares)?;
+            if emit {
+                evm::log(Erc20::Transfer {
+                    from,
+                    to: fee_recipient,
+                    amount: fee,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets an allowance, replacing any expiration or spend cap on the
+    /// previous one. The new allowance expires after `DEFAULT_ALLOWANCE_TTL`,
+    /// if set.
+    fn set_approval(&mut self, owner: Address, spender: Address, amount: U256) -> Erc20Result<()> {
+        // Zeroing an allowance that is already zero needs no writes.
+        if amount != U256::ZERO || self.allowances.get(owner).get(spender) != U256::ZERO {
+            let expiration =
+                U::DEFAULT_ALLOWANCE_TTL.map_or(0, |ttl| block::timestamp().saturating_add(ttl));
+            self.write_allowance(owner, spender, amount)?;
+            self.allowance_expirations
+                .setter(owner)
+                .setter(spender)
+                .set(U64::from(expiration));
+            self.spend_caps
+                .setter(owner)
+                .setter(spender)
+                .set(U256::ZERO);
+        }
+
+        evm::log(Erc20::Approval {
+            owner,
+            spender,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Updates an allowance amount, leaving its expiration intact.
+    fn write_allowance(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Erc20Result<()> {
+        let mut allowance = self.allowances.setter(owner);
+        let mut allowance = allowance.setter(spender);
+        let old = allowance.get();
+        allowance.set(amount);
+
+        if old == U256::ZERO && amount != U256::ZERO {
+            let count = self.approval_counts.get(owner);
+            self.approval_counts.insert(owner, count + U256::from(1));
+        } else if old != U256::ZERO && amount == U256::ZERO {
+            let count = self.approval_counts.get(owner);
+            self.approval_counts.insert(owner, count - U256::from(1));
+        }
+        self.record_allowance_change(owner, spender, amount);
+        Ok(())
+    }
+
+    /// Appends to the allowance's ring buffer, if `ALLOWANCE_HISTORY_LEN` is
+    /// set, overwriting the oldest entry once it is full.
+    fn record_allowance_change(&mut self, owner: Address, spender: Address, amount: U256) {
+        if U::ALLOWANCE_HISTORY_LEN == 0 {
+            return;
+        }
+        let count = self.allowance_change_counts.get(owner).get(spender);
+        let slot = U256::from(count.to::<u64>() % U::ALLOWANCE_HISTORY_LEN);
+
+        self.allowance_change_amounts
+            .setter(owner)
+            .setter(spender)
+            .insert(slot, amount);
+        self.allowance_change_blocks
+            .setter(owner)
+            .setter(spender)
+            .insert(slot, U64::from(block::number()));
+        self.allowance_change_counts
+            .setter(owner)
+            .insert(spender, count + U64::from(1));
+    }
+
+    fn _last_allowance_changes(
+        &self,
+        owner: Address,
+        spender: Address,
+        count: u64,
+    ) -> Vec<(U256, u64)> {
+        if U::ALLOWANCE_HISTORY_LEN == 0 {
+            return Vec::new();
+        }
+        let total = self
+            .allowance_change_counts
+            .get(owner)
+            .get(spender)
+            .to::<u64>();
+        let count = count.min(total).min(U::ALLOWANCE_HISTORY_LEN);
+
+        (1..=count)
+            .map(|back| {
+                let slot = U256::from((total - back) % U::ALLOWANCE_HISTORY_LEN);
+                let amount = self
+                    .allowance_change_amounts
+                    .get(owner)
+                    .get(spender)
+                    .get(slot);
+                let block = self
+                    .allowance_change_blocks
+                    .get(owner)
+                    .get(spender)
+                    .get(slot);
+                (amount, block.to::<u64>())
+            })
+            .collect()
+    }
+
+    /// The permit nonce, held in the low bits of the account's packed data.
+    fn nonce_of(&self, owner: Address) -> U256 {
+        self.account_data.get(owner) & nonce_mask()
+    }
+
+    /// The nonce a permit from `owner` to `spender` must sign over, which is
+    /// per spender under `PER_SPENDER_NONCES`.
+    fn permit_nonce(&self, owner: Address, spender: Address) -> U256 {
+        if T::PER_SPENDER_NONCES {
+            self.spender_nonces.getter(owner).get(spender)
+        } else {
+            self.nonce_of(owner)
+        }
+    }
+
+    fn increment_permit_nonce(&mut self, owner: Address, spender: Address) -> Erc20Result<()> {
+        if !T::PER_SPENDER_NONCES {
+            return self.increment_nonce(owner);
+        }
+        let mut nonce = self.spender_nonces.setter(owner);
+        let mut nonce = nonce.setter(spender);
+        let next = nonce.get();
+        nonce.set(next + U256::from(1));
+        Ok(())
+    }
+
+    /// Flags held in the high bits of the account's packed data, alongside the
+    /// nonce so a permit touches a single slot. Their meaning is up to
+    /// extensions.
+    pub fn account_flags(&self, owner: Address) -> u64 {
+        (self.account_data.get(owner) >> NONCE_BITS).to::<u64>()
+    }
+
+    /// Storage for `TransferHook` implementations, under keys of their
+    /// choosing, e.g. a hash of the extension's name.
+    pub fn hook_data(&self, key: FixedBytes<32>) -> U256 {
+        self.hook_data.get(key)
+    }
+
+    /// Overwrites a `hook_data` entry.
+    pub fn set_hook_data(&mut self, key: FixedBytes<32>, value: U256) {
+        self.hook_data.insert(key, value);
+    }
+
+    /// Overwrites an account's flags, preserving its nonce.
+    pub fn set_account_flags(&mut self, owner: Address, flags: u64) {
+        let mut data = self.account_data.setter(owner);
+        let nonce = data.get() & nonce_mask();
+        data.set(nonce | (U256::from(flags) << NONCE_BITS));
+    }
+
+    fn increment_nonce(&mut self, owner: Address) -> Erc20Result<()> {
+        // The nonce can't realistically reach 2^192 and carry into the flags.
+        let mut data = self.account_data.setter(owner);
+        let next = data.get();
+        data.set(next + U256::from(1));
+        Ok(())
+    }
+
+    /// Flips `nonce`'s bit in `owner`'s unordered bitmap, failing if it was
+    /// already set.
+    fn use_unordered_nonce(&mut self, owner: Address, nonce: U256) -> Erc20Result<()> {
+        let bit = U256::from(1) << (nonce.byte(0) as usize);
+        let mut word = self.nonce_bitmaps.setter(owner);
+        let mut word = word.setter(nonce >> 8);
+        let bits = word.get();
+        if bits & bit != U256::ZERO {
+            return Err(Erc20::Erc20Errors::NonceAlreadyUsed(Default::default()));
+        }
+        word.set(bits | bit);
+        Ok(())
+    }
+
+    /// Requires the owner as caller and, under `TIMELOCK_DELAY`, that the
+    /// call is being run by `execute`.
+    fn only_owner(&self) -> Erc20Result<()> {
+        self.only_owner_caller()?;
+        if U::TIMELOCK_DELAY.is_some() && !self.timelock_executing.get() {
+            return Err(Erc20::Erc20Errors::TimelockRequired(Default::default()));
+        }
+        Ok(())
+    }
+
+    fn only_owner_caller(&self) -> Erc20Result<()> {
+        if msg::sender() != self.owner.get() {
+            return Err(Erc20::Erc20Errors::Unauthorized(Default::default()));
+        }
+        Ok(())
+    }
+
+    fn _queue(&mut self, data: &[u8]) -> Erc20Result<FixedBytes<32>> {
+        self.only_owner_caller()?;
+        let id = keccak256(data);
+        let eta = block::timestamp().saturating_add(U::TIMELOCK_DELAY.unwrap_or_default());
+        self.timelock_etas.insert(id, U64::from(eta));
+
+        evm::log(Erc20::CallQueued { id: *id, eta });
+        Ok(id)
+    }
+
+    fn _cancel(&mut self, data: &[u8]) -> Erc20Result<()> {
+        self.only_owner_caller()?;
+        let id = keccak256(data);
+        if self.timelock_etas.get(id) == U64::ZERO {
+            return Err(Erc20::Erc20Errors::CallNotQueued(Default::default()));
+        }
+        self.timelock_etas.insert(id, U64::ZERO);
+
+        evm::log(Erc20::CallCanceled { id: *id });
+        Ok(())
+    }
+
+    /// Consumes a ready queued call and lets owner-only checks pass while it
+    /// runs.
+    fn begin_execute(&mut self, data: &[u8]) -> Erc20Result<()> {
+        self.only_owner_caller()?;
+        let id = keccak256(data);
+        let eta = self.timelock_etas.get(id);
+        if eta == U64::ZERO {
+            return Err(Erc20::Erc20Errors::CallNotQueued(Default::default()));
+        }
+        if U64::from(block::timestamp()) < eta {
+            return Err(Erc20::Erc20Errors::CallNotReady(Default::default()));
+        }
+        self.timelock_etas.insert(id, U64::ZERO);
+        self.timelock_executing.set(true);
+
+        evm::log(Erc20::CallExecuted { id: *id });
+        Ok(())
+    }
+
+    fn _name(&self) -> String {
+        if U::MUTABLE_METADATA {
+            return String::from_utf8_lossy(&self.name.0.get_bytes()).into_owned();
+        }
+        U::NAME.to_owned()
+    }
+
+    fn _symbol(&self) -> String {
+        if U::MUTABLE_METADATA {
+            return String::from_utf8_lossy(&self.symbol.0.get_bytes()).into_owned();
+        }
+        U::SYMBOL.to_owned()
+    }
+
+    fn set_owner(&mut self, new_owner: Address) {
+        let previous = self.owner.get();
+        self.owner.set(new_owner);
+        self.pending_owner.set(Address::ZERO);
+
+        evm::log(Erc20::OwnershipTransferred {
+            previousOwner: previous,
+            newOwner: new_owner,
+        });
+    }
+
+    fn _transfer_ownership(&mut self, new_owner: Address) -> Erc20Result<()> {
+        self.only_owner()?;
+        self.pending_owner.set(new_owner);
+
+        evm::log(Erc20::OwnershipTransferStarted {
+            previousOwner: self.owner.get(),
+            newOwner: new_owner,
+        });
+        Ok(())
+    }
+
+    fn _accept_ownership(&mut self) -> Erc20Result<()> {
+        let sender = msg::sender();
+        if sender != self.pending_owner.get() {
+            return Err(Erc20::Erc20Errors::Unauthorized(Default::default()));
+        }
+        self.set_owner(sender);
+        Ok(())
+    }
+
+    fn _renounce_ownership(&mut self, confirm: bool) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !confirm {
+            return Err(Erc20::Erc20Errors::RenounceNotConfirmed(Default::default()));
+        }
+        self.set_owner(Address::ZERO);
+        Ok(())
+    }
+
+    /// Stored decimals take effect once set, so a stored zero is honored
+    /// while an unset slot falls back to `DECIMALS`.
+    fn _decimals(&self) -> u8 {
+        if U::MUTABLE_METADATA && self.decimals_set.get() {
+            return self.decimals.get().to::<u8>();
+        }
+        U::DECIMALS
+    }
+
+    fn _set_decimals(&mut self, decimals: u8) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !U::MUTABLE_METADATA || self.decimals_set.get() {
+            return Err(Erc20::Erc20Errors::MetadataImmutable(Default::default()));
+        }
+        self.decimals.set(U8::from(decimals));
+        self.decimals_set.set(true);
+        Ok(())
+    }
+
+    fn _set_name(&mut self, name: String) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !U::MUTABLE_METADATA {
+            return Err(Erc20::Erc20Errors::MetadataImmutable(Default::default()));
+        }
+        self.name.set_str(name);
+        Ok(())
+    }
+
+    fn _set_symbol(&mut self, symbol: String) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !U::MUTABLE_METADATA {
+            return Err(Erc20::Erc20Errors::MetadataImmutable(Default::default()));
+        }
+        self.symbol.set_str(symbol);
+        Ok(())
+    }
+
+    fn _total_supply(&self) -> U256 {
+        self.amount_for(self.total_supply.get())
+    }
+
+    fn _balance_of(&self, owner: Address) -> U256 {
+        self.amount_for(self.balances.get(owner))
+    }
+
+    /// Records the current total supply against the current block,
+    /// overwriting any earlier checkpoint from the same block.
+    fn checkpoint_supply(&mut self) {
+        let block = U64::from(block::number());
+        let supply = self._total_supply();
+
+        let len = self.checkpoint_blocks.len();
+        if len > 0 && self.checkpoint_blocks.get(len - 1) == Some(block) {
+            if let Some(mut last) = self.checkpoint_supplies.setter(len - 1) {
+                last.set(supply);
+            }
+            return;
+        }
+        self.checkpoint_blocks.push(block);
+        self.checkpoint_supplies.push(supply);
+    }
+
+    /// Binary searches for the last checkpoint at or before `block_number`.
+    fn _total_supply_at_block(&self, block_number: u64) -> U256 {
+        let target = U64::from(block_number);
+        let (mut low, mut high) = (0, self.checkpoint_blocks.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.checkpoint_blocks.get(mid).unwrap_or_default() <= target {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == 0 {
+            return U256::ZERO;
+        }
+        self.checkpoint_supplies.get(low - 1).unwrap_or_default()
+    }
+
+    fn _rebase(&mut self, new_index: U256) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !U::REBASING || new_index == U256::ZERO {
+            return Err(Erc20::Erc20Errors::InvalidRebase(Default::default()));
+        }
+        // The supply must stay representable as a token amount, or every
+        // conversion of it would panic.
+        if checked_mul_div_floor(self.total_supply.get(), new_index, REBASE_PRECISION).is_none() {
+            return Err(Erc20::Erc20Errors::InvalidRebase(Default::default()));
+        }
+
+        let old_index = self.current_index();
+        self.rebase_index.set(new_index);
+        self.checkpoint_supply();
+
+        evm::log(Erc20::Rebase {
+            oldIndex: old_index,
+            newIndex: new_index,
+        });
+        Ok(())
+    }
+
+    fn _transfer(&mut self, to: Address, amount: U256) -> Erc20Result<bool> {
+        self.move_tokens(msg::sender(), to, amount)?;
+        Ok(true)
+    }
+
+    fn _transfer_quiet(&mut self, to: Address, amount: U256) -> Erc20Result<bool> {
+        self.move_tokens_with(msg::sender(), to, amount, false)?;
+        Ok(true)
+    }
+
+    fn _transfer_batch(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Erc20Result<bool> {
+        if recipients.len() != amounts.len() {
+            return Err(Erc20::Erc20Errors::LengthMismatch(Default::default()));
+        }
+
+        let sender = msg::sender();
+        for (to, amount) in recipients.iter().zip(amounts.iter()) {
+            U::before_token_transfer(self, sender, *to, *amount)?;
+        }
+        // Debit per recipient so each credit moves exactly the shares taken.
+        for (to, amount) in recipients.into_iter().zip(amounts) {
+            let shares = self.debit(sender, amount)?;
+            self.deliver(sender, to, amount, shares, true)?;
+            U::after_token_transfer(self, sender, to, amount)?;
+        }
+
+        Ok(true)
+    }
+
+    fn _transfer_batch_optimized(
+        &mut self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Erc20Result<bool> {
+        if recipients.len() != amounts.len() {
+            return Err(Erc20::Erc20Errors::LengthMismatch(Default::default()));
+        }
+
+        let (recipients, amounts) = merge_transfers(recipients, amounts)?.into_iter().unzip();
+        self._transfer_batch(recipients, amounts)
+    }
+
+    /// Reads an allowance, which is zero once its expiration (if any) has
+    /// passed.
+    fn _allowance(&self, owner: Address, spender: Address) -> U256 {
+        let expiration = self.allowance_expirations.get(owner).get(spender);
+        if expiration != U64::ZERO && U64::from(block::timestamp()) > expiration {
+            return U256::ZERO;
+        }
+        self.allowances.get(owner).get(spender)
+    }
+
+    fn _sweep_expired_allowance(&mut self, owner: Address, spender: Address) -> Erc20Result<()> {
+        let expiration = self.allowance_expirations.get(owner).get(spender);
+        if expiration == U64::ZERO || U64::from(block::timestamp()) <= expiration {
+            return Err(Erc20::Erc20Errors::AllowanceNotExpired(Default::default()));
+        }
+        self.set_approval(owner, spender, U256::ZERO)
+    }
+
+    /// The allowance an `approve`-style call of `amount` results in: `amount`
+    /// itself, or under `APPROVE_IS_CUMULATIVE`, added to the current one.
+    fn approved_amount(&self, owner: Address, spender: Address, amount: U256) -> Erc20Result<U256> {
+        if !U::APPROVE_IS_CUMULATIVE || amount == U256::ZERO {
+            return Ok(amount);
+        }
+        self._allowance(owner, spender)
+            .checked_add(amount)
+            .ok_or(Erc20::Erc20Errors::AllowanceOverflow(Default::default()))
+    }
+
+    fn _approve(&mut self, spender: Address, amount: U256) -> Erc20Result<bool> {
+        let owner = msg::sender();
+        let amount = self.approved_amount(owner, spender, amount)?;
+        self.set_approval(owner, spender, amount)?;
+        Ok(true)
+    }
+
+    fn _approve_with_deadline(
+        &mut self,
+        spender: Address,
+        amount: U256,
+        deadline: u64,
+    ) -> Erc20Result<bool> {
+        // A zero expiration means the allowance never expires.
+        let now = block::timestamp();
+        if deadline == 0 || deadline < now {
+            return Err(Erc20::Erc20Errors::InvalidDeadline(
+                Erc20::InvalidDeadline {
+                    deadline,
+                    blockTimestamp: now,
+                },
+            ));
+        }
+        let deadline =
+            U::DEFAULT_ALLOWANCE_TTL.map_or(deadline, |ttl| deadline.min(now.saturating_add(ttl)));
+
+        let owner = msg::sender();
+        let allowance = self.approved_amount(owner, spender, amount)?;
+        self.set_approval(owner, spender, allowance)?;
+        self.allowance_expirations
+            .setter(owner)
+            .setter(spender)
+            .set(U64::from(deadline));
+        Ok(true)
+    }
+
+    fn _increase_allowance(&mut self, spender: Address, added_value: U256) -> Erc20Result<bool> {
+        let owner = msg::sender();
+        let value = self
+            ._allowance(owner, spender)
+            .checked_add(added_value)
+            .ok_or(Erc20::Erc20Errors::AllowanceOverflow(Default::default()))?;
+        self.adjust_allowance(owner, spender, value)?;
+        Ok(true)
+    }
+
+    fn _decrease_allowance(
+        &mut self,
+        spender: Address,
+        subtracted_value: U256,
+    ) -> Erc20Result<bool> {
+        let owner = msg::sender();
+        let value = self
+            ._allowance(owner, spender)
+            .checked_sub(subtracted_value)
+            .ok_or(Erc20::Erc20Errors::AllowanceUnderflow(Default::default()))?;
+        self.adjust_allowance(owner, spender, value)?;
+        Ok(true)
+    }
+
+    /// Consumes `amount` of `owner`'s allowance for `spender`, enforcing any
+    /// spend cap. An infinite (`U256::MAX`) allowance is never decremented.
+    /// Zero amounts consume nothing.
+    fn spend_allowance(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Erc20Result<()> {
+        if amount == U256::ZERO {
+            return Ok(());
+        }
+
+        let allowance = self._allowance(owner, spender);
+        if allowance < amount {
+            return Err(Erc20::Erc20Errors::InsufficientAllowance(Default::default()));
+        }
+        let cap = self.spend_caps.get(owner).get(spender);
+        if cap != U256::ZERO && amount > cap {
+            return Err(Erc20::Erc20Errors::SpendCapExceeded(Default::default()));
+        }
+        if allowance == U256::MAX {
+            return Ok(());
+        }
+
+        self.adjust_allowance(owner, spender, allowance - amount)
+    }
+
+    /// Changes an allowance's amount, keeping its expiration and spend cap,
+    /// and logs the new amount.
+    fn adjust_allowance(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Erc20Result<()> {
+        self.write_allowance(owner, spender, amount)?;
+        evm::log(Erc20::Approval {
+            owner,
+            spender,
+            amount,
+        });
+        Ok(())
+    }
+
+    fn _transfer_from(&mut self, from: Address, to: Address, amount: U256) -> Erc20Result<bool> {
+        self.spend_allowance(from, msg::sender(), amount)?;
+        self.move_tokens(from, to, amount)?;
+        Ok(true)
+    }
+
+    fn _burn_from(&mut self, from: Address, amount: U256) -> Erc20Result<()> {
+        self.spend_allowance(from, msg::sender(), amount)?;
+        self._burn(from, amount)
+    }
+
+    fn _transfer_and_call(
+        &mut self,
+        to: Address,
+        amount: U256,
+        data: Vec<u8>,
+    ) -> Erc20Result<bool> {
+        let sender = msg::sender();
+        self.move_tokens(sender, to, amount)?;
+
+        if token::has_code(to) {
+            self.non_reentrant(|_| {
+                match erc1363::on_transfer_received(to, sender, sender, amount, data) {
+                    Ok(true) => Ok(()),
+                    _ => Err(Erc20Errors::ReceiverRejected(Erc20::ReceiverRejected {
+                        receiver: to,
+                    })),
+                }
+            })?;
+        }
+        Ok(true)
+    }
+
+    fn _transfer_or_credit(&mut self, to: Address, amount: U256) -> Erc20Result<bool> {
+        let sender = msg::sender();
+        let before = self.balances.get(to);
+        self.move_tokens(sender, to, amount)?;
+
+        if !token::has_code(to) {
+            return Ok(true);
+        }
+        let accepted = self.non_reentrant(|_| {
+            Ok(matches!(
+                erc1363::on_transfer_received(to, sender, sender, amount, vec![]),
+                Ok(true)
+            ))
+        })?;
+        if accepted {
+            return Ok(true);
+        }
+
+        // The SDK reverts reentrant calls, so the receiver can't have moved
+        // the tokens. Take back what it was delivered, net of any fee, as a
+        // regular transfer, and credit the shares that arrive.
+        let this = contract::address();
+        let received = self.amount_for(self.balances.get(to).saturating_sub(before));
+        let pooled = self.balances.get(this);
+        self.move_tokens(to, this, received)?;
+        let credited = self.balances.get(this) - pooled;
+
+        let mut credits = self.credits.setter(to);
+        let total = credits.get() + credited;
+        credits.set(total);
+        Ok(true)
+    }
+
+    fn _withdraw(&mut self) -> Erc20Result<U256> {
+        let account = msg::sender();
+        let shares = self.credits.get(account);
+        if shares == U256::ZERO {
+            return Ok(U256::ZERO);
+        }
+        self.credits.insert(account, U256::ZERO);
+
+        // Rounding down means this never takes more than the account's own
+        // shares out of the pool.
+        let amount = self.amount_for(shares);
+        self.move_tokens(contract::address(), account, amount)?;
+        Ok(amount)
+    }
+
+    fn _approve_and_call(
+        &mut self,
+        spender: Address,
+        amount: U256,
+        data: Vec<u8>,
+    ) -> Erc20Result<bool> {
+        let owner = msg::sender();
+        let allowance = self.approved_amount(owner, spender, amount)?;
+        self.set_approval(owner, spender, allowance)?;
+
+        if token::has_code(spender) {
+            self.non_reentrant(|_| {
+                match erc1363::on_approval_received(spender, owner, amount, data) {
+                    Ok(true) => Ok(()),
+                    _ => Err(Erc20Errors::ReceiverRejected(Erc20::ReceiverRejected {
+                        receiver: spender,
+                    })),
+                }
+            })?;
+        }
+        Ok(true)
+    }
+
+    fn _authorize_operator(&mut self, operator: Address) -> Erc20Result<()> {
+        let holder = msg::sender();
+        self.operators.setter(holder).insert(operator, true);
+        evm::log(Erc20::AuthorizedOperator { operator, holder });
+        Ok(())
+    }
+
+    fn _revoke_operator(&mut self, operator: Address) -> Erc20Result<()> {
+        let holder = msg::sender();
+        self.operators.setter(holder).insert(operator, false);
+        evm::log(Erc20::RevokedOperator { operator, holder });
+        Ok(())
+    }
+
+    fn _is_operator_for(&self, operator: Address, holder: Address) -> bool {
+        operator == holder || self.operators.get(holder).get(operator)
+    }
+
+    fn _operator_transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        amount: U256,
+        data: Vec<u8>,
+    ) -> Erc20Result<()> {
+        let operator = msg::sender();
+        if !self._is_operator_for(operator, from) {
+            return Err(Erc20::Erc20Errors::Unauthorized(Default::default()));
+        }
+        self.move_tokens(from, to, amount)?;
+
+        if token::has_code(to) {
+            self.non_reentrant(|_| {
+                match erc1363::on_transfer_received(to, operator, from, amount, data) {
+                    Ok(true) => Ok(()),
+                    _ => Err(Erc20Errors::ReceiverRejected(Erc20::ReceiverRejected {
+                        receiver: to,
+                    })),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn _set_fee_recipient(&mut self, recipient: Address) -> Erc20Result<()> {
+        self.only_owner()?;
+        self.fee_recipient.set(recipient);
+        Ok(())
+    }
+
+    fn _freeze(&mut self, addr: Address, amount: U256) -> Erc20Result<()> {
+        self.only_owner()?;
+        let mut frozen = self.frozen.setter(addr);
+        let new_frozen = frozen.get().saturating_add(amount);
+        frozen.set(new_frozen);
+        Ok(())
+    }
+
+    fn _unfreeze(&mut self, addr: Address, amount: U256) -> Erc20Result<()> {
+        self.only_owner()?;
+        let mut frozen = self.frozen.setter(addr);
+        let new_frozen = frozen.get().saturating_sub(amount);
+        frozen.set(new_frozen);
+        Ok(())
+    }
+
+    fn _owner_mint_saturating(&mut self, to: Address, amount: U256) -> Erc20Result<U256> {
+        self.only_owner()?;
+        self._mint_saturating(to, amount)
+    }
+
+    fn _slash(&mut self, from: Address, amount: U256) -> Erc20Result<U256> {
+        self.only_owner()?;
+        self._burn_saturating(from, amount)
+    }
+
+    fn _mint_with_permit(
+        &mut self,
+        owner: Address,
         amount: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        let Some(collateral) = U::COLLATERAL else {
+            return Err(Erc20::Erc20Errors::NoCollateral(Default::default()));
+        };
+        let this = contract::address();
+
+        self.non_reentrant(|_| {
+            let failed =
+                || Erc20Errors::TokenCallFailed(Erc20::TokenCallFailed { token: collateral });
+            if !token::permit_or_allowed(collateral, owner, this, amount, deadline, v, r, s) {
+                return Err(failed());
+            }
+            match token::transfer_from(collateral, owner, this, amount) {
+                Ok(true) => Ok(()),
+                _ => Err(failed()),
+            }
+        })?;
+
+        self._mint(owner, amount)
+    }
+
+    fn in_migration(&self) -> bool {
+        self.migration_used.get() && U64::from(block::number()) <= self.migration_end_block.get()
+    }
+
+    fn _enable_migration_mode(&mut self) -> Erc20Result<()> {
+        self.only_owner()?;
+        if U::MIGRATION_WINDOW_BLOCKS == 0 || self.migration_used.get() {
+            return Err(Erc20::Erc20Errors::MigrationInactive(Default::default()));
+        }
+        let end_block = block::number().saturating_add(U::MIGRATION_WINDOW_BLOCKS);
+        self.migration_used.set(true);
+        self.migration_end_block.set(U64::from(end_block));
+
+        evm::log(Erc20::MigrationModeEnabled {
+            endBlock: end_block,
+        });
+        Ok(())
+    }
+
+    fn _emergency_set_balance(&mut self, account: Address, amount: U256) -> Erc20Result<()> {
+        self.only_owner()?;
+        if !self.in_migration() {
+            return Err(Erc20::Erc20Errors::MigrationInactive(Default::default()));
+        }
+
+        let old_shares = self.balances.get(account);
+        let new_shares = self.shares_for(amount);
+        let old_amount = self.amount_for(old_shares);
+        let total = self.total_supply.get();
+        let new_total = if new_shares >= old_shares {
+            total
+                .checked_add(new_shares - old_shares)
+                .ok_or(Erc20::Erc20Errors::BalanceOverflow(Default::default()))?
+        } else {
+            total - (old_shares - new_shares)
+        };
+        self.set_balance(account, old_shares, new_shares);
+        self.total_supply.set(new_total);
+
+        if new_shares >= old_shares {
+            let minted = amount.saturating_sub(old_amount);
+            evm::log(Erc20::Transfer {
+                from: Address::ZERO,
+                to: account,
+                amount: minted,
+            });
+            #[cfg(feature = "mint-burn-events")]
+            evm::log(Erc20::Mint {
+                to: account,
+                amount: minted,
+            });
+        } else {
+            let burned = old_amount.saturating_sub(amount);
+            evm::log(Erc20::Transfer {
+                from: account,
+                to: Address::ZERO,
+                amount: burned,
+            });
+            #[cfg(feature = "mint-burn-events")]
+            evm::log(Erc20::Burn {
+                from: account,
+                amount: burned,
+            });
+        }
+        self.checkpoint_supply();
+        Ok(())
+    }
+
+    fn _set_domain_salt(&mut self, salt: FixedBytes<32>) -> Erc20Result<()> {
+        self.only_owner()?;
+        self.domain_salt.set(salt);
+        self.domain_salt_set.set(true);
+        // Force the cached separator to be recomputed with the new salt.
+        self.cached_chain_id.set(U64::ZERO);
+
+        evm::log(Erc20::EIP712DomainChanged {});
+        Ok(())
+    }
+
+    fn _rescue_tokens(&mut self, token: Address, to: Address, amount: U256) -> Erc20Result<()> {
+        self.only_owner()?;
+        if token == contract::address() {
+            return Err(Erc20::Erc20Errors::CannotRescueSelf(Default::default()));
+        }
+
+        self.non_reentrant(|_| match token::transfer(token, to, amount) {
+            Ok(true) => Ok(()),
+            _ => Err(Erc20Errors::TokenCallFailed(Erc20::TokenCallFailed {
+                token,
+            })),
+        })
+    }
+
+    fn _sweep_eth(&mut self, to: Address) -> Erc20Result<U256> {
+        self.only_owner()?;
+
+        self.non_reentrant(|_| {
+            let amount = contract::balance();
+            if amount != U256::ZERO {
+                call::transfer_eth(to, amount)
+                    .map_err(|_| Erc20Errors::EthTransferFailed(Erc20::EthTransferFailed { to }))?;
+            }
+            Ok(amount)
+        })
+    }
+
+    /// Checks that a signed deadline has not passed, and is within
+    /// `MAX_DEADLINE_WINDOW` if one is configured.
+    fn check_deadline(&self, deadline: U256) -> Erc20Result<()> {
+        check_deadline_at(
+            deadline,
+            U256::from(block::timestamp()),
+            T::MAX_DEADLINE_WINDOW,
+        )
+    }
+
+    /// Checks that `signer` signed `data` against this contract's EIP-712
+    /// domain.
+    fn verify_signed<S: SolStruct>(
+        &mut self,
+        data: &S,
+        signer: Address,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        let hash = self.signing_digest(data, signer)?;
+        if self.recover(hash, v, r, s)? != signer {
+            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+        }
+        Ok(())
+    }
+
+    /// The EIP-712 digest `signer` signs for `data`, refreshing the domain
+    /// separator cache. The refresh recomputes the separator whenever the
+    /// chain id has changed, which is what stops replay across a fork.
+    fn signing_digest<S: SolStruct>(
+        &mut self,
+        data: &S,
+        signer: Address,
+    ) -> Erc20Result<FixedBytes<32>> {
+        let chain_id = chainid();
+        let separator = self.refresh_domain_separator(chain_id);
+        let separator = self
+            .owner_domain_separator(chain_id, signer)
+            .unwrap_or(separator);
+        Ok(eip712_digest(separator, data))
+    }
+
+    /// Recovers the signer of `hash`.
+    fn recover(&self, hash: FixedBytes<32>, v: u8, r: U256, s: U256) -> Erc20Result<Address> {
+        let v = normalize_v(v).ok_or(Erc20::Erc20Errors::InvalidPermit(Default::default()))?;
+        ecrecover(T::ECRECOVER, hash, v, r, s)
+            .map_err(|_| Erc20Errors::InvalidPermit(Default::default()))
+    }
+
+    fn _permit(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
+
+        let permit = self.build_permit(owner, spender, value, deadline);
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        self.set_approval(owner, spender, value)?;
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
+    }
+
+    fn _permit_multisig(
+        &mut self,
+        owner_set: Vec<Address>,
+        threshold: u8,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        signatures: &[u8],
+    ) -> Erc20Result<()> {
+        // Ascending order makes the account unique per set and rules out
+        // repeated members.
+        let ascending = owner_set.windows(2).all(|w| w[0] < w[1]);
+        if !ascending || threshold == 0 || threshold as usize > owner_set.len() {
+            return Err(Erc20::Erc20Errors::InvalidOwner(Default::default()));
+        }
+        if spender == Address::ZERO {
+            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+        }
+        if !signatures.len().is_multiple_of(65) {
+            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+        }
+        self.check_deadline(deadline)?;
+
+        let owner = multisig_account(&owner_set, threshold);
+        let permit = self.build_permit(owner, spender, value, deadline);
+        let hash = self.signing_digest(&permit, owner)?;
+
+        let mut approved = vec![false; owner_set.len()];
+        for sig in signatures.chunks(65) {
+            let (r, s) = split_rs(&sig[..64])
+                .ok_or(Erc20::Erc20Errors::InvalidPermit(Default::default()))?;
+            let signer = self.recover(hash, sig[64], r, s)?;
+            let member = owner_set
+                .binary_search(&signer)
+                .map_err(|_| Erc20::Erc20Errors::InvalidPermit(Default::default()))?;
+            approved[member] = true;
+        }
+        if approved.iter().filter(|&&a| a).count() < threshold as usize {
+            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+        }
+
+        self.set_approval(owner, spender, value)?;
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
+    }
+
+    fn _permit_packed(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: U256,
+        rs: &[u8],
+    ) -> Erc20Result<()> {
+        let v = normalize_v_wide(v).ok_or(Erc20::Erc20Errors::InvalidPermit(Default::default()))?;
+        let (r, s) = split_rs(rs).ok_or(Erc20::Erc20Errors::InvalidPermit(Default::default()))?;
+        self._permit(owner, spender, value, deadline, v, r, s)
+    }
+
+    fn _permit_strict(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        allow_decrease: bool,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        if !allow_decrease && value < self._allowance(owner, spender) {
+            return Err(Erc20::Erc20Errors::AllowanceDecrease(Default::default()));
+        }
+        self._permit(owner, spender, value, deadline, v, r, s)
+    }
+
+    fn _permit_increase(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        added_value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
+
+        let permit = PermitIncrease {
+            owner,
+            spender,
+            addedValue: added_value,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        let value = self
+            ._allowance(owner, spender)
+            .checked_add(added_value)
+            .ok_or(Erc20::Erc20Errors::AllowanceOverflow(Default::default()))?;
+        self.adjust_allowance(owner, spender, value)?;
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
+    }
+
+    fn _permit_with_expiry(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        expiration: u64,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
+
+        let permit = PermitWithExpiry {
+            owner,
+            spender,
+            value,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+            expiration,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        self.set_approval(owner, spender, value)?;
+        self.allowance_expirations
+            .setter(owner)
+            .setter(spender)
+            .set(U64::from(expiration));
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
+    }
+
+    fn _permit_with_cap(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        max_spend_per_tx: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
+
+        let permit = PermitWithCap {
+            owner,
+            spender,
+            value,
+            maxSpendPerTx: max_spend_per_tx,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        self.set_approval(owner, spender, value)?;
+        self.spend_caps
+            .setter(owner)
+            .setter(spender)
+            .set(max_spend_per_tx);
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
+    }
+
+    fn _permit_multi(
+        &mut self,
+        owner: Address,
+        spenders: Vec<Address>,
+        values: Vec<U256>,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &spenders)?;
+        if spenders.len() != values.len() {
+            return Err(Erc20::Erc20Errors::LengthMismatch(Default::default()));
+        }
+        self.check_deadline(deadline)?;
 
+        let permit = PermitMulti {
+            owner,
+            spenders,
+            values,
+            nonce: self.nonce_of(owner),
+            deadline,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        for (&spender, &value) in permit.spenders.iter().zip(&permit.values) {
+            self.set_approval(owner, spender, value)?;
+            evm::log(Erc20::PermitUsed {
+                owner,
+                spender,
+                value,
+                nonce: permit.nonce,
+            });
+        }
+        self.increment_nonce(owner)
+    }
+
+    fn _permit_with_relayer_fee(
+        &mut self,
         owner: Address,
         spender: Address,
         value: U256,
+        relayer: Address,
+        relayer_fee: U256,
         deadline: U256,
         v: u8,
         r: U256,
         s: U256,
-    ) -> Result<bool, Vec<u8>> {
-        self._transfer_with_permit(to, amount, owner, spender, value, deadline, v, r, s)
-            .map_err(|e| e.encode())
-    }
-}
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
 
-impl<T, U> Erc20Permit<T, U>
-where
-    T: DomainInfo,
-    U: Erc20Details,
-{
-    pub fn _mint(&mut self, to: Address, amount: U256) -> Erc20Result<()> {
-        let total = self.total_supply.get();
+        let permit = PermitWithRelayerFee {
+            owner,
+            spender,
+            value,
+            relayer,
+            relayerFee: relayer_fee,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        self.set_approval(owner, spender, value)?;
+        self.increment_permit_nonce(owner, spender)?;
 
-        self.saturating_credit(to, amount)?;
-        self.total_supply.set(total + amount);
+        if relayer_fee != U256::ZERO {
+            let relayer = if relayer == Address::ZERO {
+                msg::sender()
+            } else {
+                relayer
+            };
+            self.move_tokens(owner, relayer, relayer_fee)?;
+        }
 
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
         Ok(())
     }
 
-    pub fn _burn(&mut self, from: Address, amount: U256) -> Erc20Result<()> {
-        let total = self.total_supply.get();
+    fn _revoke_with_sig(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        if owner == Address::ZERO {
+            return Err(Erc20::Erc20Errors::InvalidOwner(Default::default()));
+        }
+        self.check_deadline(deadline)?;
+
+        let revocation = RevokeApproval {
+            owner,
+            spender,
+            nonce: self.permit_nonce(owner, spender),
+            deadline,
+        };
+        self.verify_signed(&revocation, owner, v, r, s)?;
 
-        let burned = self.saturating_debit(from, amount)?;
-        self.total_supply.set(total - burned);
+        self.set_approval(owner, spender, U256::ZERO)?;
+        self.increment_permit_nonce(owner, spender)?;
 
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value: U256::ZERO,
+            nonce: revocation.nonce,
+        });
         Ok(())
     }
 
-    fn get_domain(&self) -> Eip712Domain {
-        Eip712Domain {
-            name: T::NAME.map(std::borrow::Cow::Borrowed),
-            version: T::VERSION.map(std::borrow::Cow::Borrowed),
-            chain_id: Some(U256::from(chainid())),
-            verifying_contract: Some(contract::address()),
-            salt: T::SALT,
+    fn _permit_block_deadline(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline_block: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        let now = U256::from(block::number());
+        if now > deadline_block {
+            return Err(Erc20::Erc20Errors::PermitExpiredAtBlock(
+                Erc20::PermitExpiredAtBlock {
+                    deadlineBlock: deadline_block,
+                    blockNumber: now,
+                },
+            ));
         }
+
+        let permit = PermitBlockDeadline {
+            owner,
+            spender,
+            value,
+            nonce: self.permit_nonce(owner, spender),
+            deadlineBlock: deadline_block,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
+
+        self.set_approval(owner, spender, value)?;
+        self.increment_permit_nonce(owner, spender)?;
+
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce: permit.nonce,
+        });
+        Ok(())
     }
 
-    /// Debits an account with the given amount, saturating the balance, and
-    /// returning the amount actually debited.
-    fn saturating_debit(&mut self, addr: Address, amount: U256) -> Erc20Result<U256> {
-        let mut balance = self.balances.setter(addr);
+    fn _permit_with_nonce(
+        &mut self,
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        nonce: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        check_permit_parties(owner, &[spender])?;
+        self.check_deadline(deadline)?;
 
-        let new_bal = balance.get().saturating_sub(amount);
-        let burned = balance.get() - new_bal;
+        let permit = UnorderedPermit {
+            owner,
+            spender,
+            value,
+            nonce,
+            deadline,
+        };
+        self.verify_signed(&permit, owner, v, r, s)?;
 
-        balance.set(new_bal);
+        self.use_unordered_nonce(owner, nonce)?;
+        self.set_approval(owner, spender, value)?;
 
-        Ok(burned)
+        evm::log(Erc20::PermitUsed {
+            owner,
+            spender,
+            value,
+            nonce,
+        });
+        Ok(())
     }
 
-    /// Debits an account with the given amount, returning an error if the
-    /// balance is insufficient.
-    fn debit(&mut self, addr: Address, amount: U256) -> Erc20Result<()> {
-        let mut balance = self.balances.setter(addr);
-
-        let bal = balance.get();
-        if bal < amount {
-            return Err(Erc20::Erc20Errors::InsufficientBalance(Default::default()));
+    fn check_authorization_unused(
+        &self,
+        authorizer: Address,
+        nonce: FixedBytes<32>,
+    ) -> Erc20Result<()> {
+        if self.authorization_states.get(authorizer).get(nonce) {
+            return Err(Erc20::Erc20Errors::AuthorizationAlreadyUsed(
+                Default::default(),
+            ));
         }
-        balance.set(bal - amount);
         Ok(())
     }
 
-    /// Credits an account with the given amount, saturating the balance, and
-    /// returning the amount actually credited.
-    fn saturating_credit(&mut self, addr: Address, amount: U256) -> Erc20Result<U256> {
-        let mut balance = self.balances.setter(addr);
+    /// Verifies and consumes an EIP-3009 transfer authorization. `auth` is
+    /// the signed struct, which determines the authorization kind.
+    fn _use_authorization<S: SolStruct>(
+        &mut self,
+        auth: &S,
+        from: Address,
+        to: Address,
+        value: U256,
+        valid_after: U256,
+        valid_before: U256,
+        nonce: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        let now = U256::from(block::timestamp());
+        if now <= valid_after {
+            return Err(Erc20::Erc20Errors::AuthorizationNotYetValid(
+                Default::default(),
+            ));
+        }
+        if now >= valid_before {
+            return Err(Erc20::Erc20Errors::AuthorizationExpired(Default::default()));
+        }
+        self.check_authorization_unused(from, nonce)?;
+        self.verify_signed(auth, from, v, r, s)?;
 
-        let new_bal = balance.get().saturating_add(amount);
-        let minted = new_bal - balance.get();
-        balance.set(new_bal);
+        self.authorization_states
+            .setter(from)
+            .setter(nonce)
+            .set(true);
+        evm::log(Erc20::AuthorizationUsed {
+            authorizer: from,
+            nonce: *nonce,
+        });
 
-        Ok(minted)
+        self.move_tokens(from, to, value)
     }
 
-    /// Credits an account with the given amount.
-    fn credit(&mut self, addr: Address, amount: U256) -> Erc20Result<()> {
-        let mut balance = self.balances.setter(addr);
+    fn _cancel_authorization(
+        &mut self,
+        authorizer: Address,
+        nonce: FixedBytes<32>,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<()> {
+        self.check_authorization_unused(authorizer, nonce)?;
+        let cancel = CancelAuthorization {
+            authorizer,
+            nonce: *nonce,
+        };
+        self.verify_signed(&cancel, authorizer, v, r, s)?;
 
-        let bal = balance.get();
-        balance.set(bal + amount);
+        self.authorization_states
+            .setter(authorizer)
+            .setter(nonce)
+            .set(true);
+        evm::log(Erc20::AuthorizationCanceled {
+            authorizer,
+            nonce: *nonce,
+        });
         Ok(())
     }
 
-    fn move_tokens(&mut self, from: Address, to: Address, amount: U256) -> Erc20Result<()> {
-        self.debit(from, amount)?;
-        self.credit(to, amount)?;
-        Ok(())
+    fn _transfer_with_permit(
+        &mut self,
+        to: Address,
+        amount: U256,
+
+        owner: Address,
+        spender: Address,
+        value: U256,
+        deadline: U256,
+        v: u8,
+        r: U256,
+        s: U256,
+    ) -> Erc20Result<bool> {
+        self._permit(owner, spender, value, deadline, v, r, s)?;
+        self._transfer_from(owner, to, amount)
     }
 
-    fn set_approval(&mut self, owner: Address, spender: Address, amount: U256) -> Erc20Result<()> {
-        self.allowances.setter(owner).setter(spender).set(amount);
-        Ok(())
+    fn _swap_with_permits(
+        &mut self,
+        maker: Address,
+        maker_amount: U256,
+        maker_deadline: U256,
+        maker_v: u8,
+        maker_r: U256,
+        maker_s: U256,
+
+        taker: Address,
+        taker_token: Address,
+        taker_amount: U256,
+        taker_deadline: U256,
+        taker_v: u8,
+        taker_r: U256,
+        taker_s: U256,
+    ) -> Erc20Result<()> {
+        if maker == Address::ZERO {
+            return Err(Erc20::Erc20Errors::InvalidOwner(Default::default()));
+        }
+        self.check_deadline(maker_deadline)?;
+        let this = contract::address();
+
+        let swap = Swap {
+            maker,
+            makerAmount: maker_amount,
+            taker,
+            takerToken: taker_token,
+            takerAmount: taker_amount,
+            nonce: self.permit_nonce(maker, this),
+            deadline: maker_deadline,
+        };
+        self.verify_signed(&swap, maker, maker_v, maker_r, maker_s)?;
+        self.increment_permit_nonce(maker, this)?;
+
+        self.move_tokens(maker, taker, maker_amount)?;
+
+        self.non_reentrant(|_| {
+            let failed =
+                || Erc20Errors::TokenCallFailed(Erc20::TokenCallFailed { token: taker_token });
+            let permitted = token::permit_or_allowed(
+                taker_token,
+                taker,
+                this,
+                taker_amount,
+                taker_deadline,
+                taker_v,
+                taker_r,
+                taker_s,
+            );
+            if !permitted {
+                return Err(failed());
+            }
+
+            // Trust the balance change rather than the token's return value.
+            let before = token::balance_of(taker_token, maker).map_err(|_| failed())?;
+            if !matches!(
+                token::transfer_from(taker_token, taker, maker, taker_amount),
+                Ok(true)
+            ) {
+                return Err(failed());
+            }
+            let after = token::balance_of(taker_token, maker).map_err(|_| failed())?;
+            if after < before || after - before < taker_amount {
+                return Err(failed());
+            }
+            Ok(())
+        })
     }
+}
 
-    fn increment_nonce(&mut self, owner: Address) -> Erc20Result<()> {
-        let mut nonce = self.nonces.setter(owner);
-        let next = nonce.get();
-        nonce.set(next + U256::from(1));
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use alloy_primitives::{address, b256};
+
+    fn domain() -> Eip712Domain {
+        Eip712Domain::new(
+            Some(Cow::Borrowed("my dumb token")),
+            Some(Cow::Borrowed("1")),
+            Some(U256::from(42161)),
+            Some(address!("1111111111111111111111111111111111111111")),
+            None,
+        )
     }
 
-    fn _total_supply(&self) -> U256 {
-        self.total_supply.get()
+    fn permit() -> Permit {
+        Permit {
+            owner: address!("2222222222222222222222222222222222222222"),
+            spender: address!("3333333333333333333333333333333333333333"),
+            value: U256::from(1000),
+            nonce: U256::from(7),
+            deadline: U256::MAX,
+        }
     }
 
-    fn _balance_of(&self, owner: Address) -> U256 {
-        self.balances.get(owner)
+    #[test]
+    fn digest_prefixes_separator_and_struct_hash() {
+        let separator = b256!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+        let struct_hash = b256!("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+
+        let mut expected = vec![0x19, 0x01];
+        expected.extend_from_slice(&separator[..]);
+        expected.extend_from_slice(&struct_hash[..]);
+
+        assert_eq!(
+            eip712_digest_of(separator, struct_hash),
+            keccak256(expected)
+        );
     }
 
-    fn _transfer(&mut self, to: Address, amount: U256) -> Erc20Result<bool> {
-        self.move_tokens(msg::sender(), to, amount)?;
-        Ok(true)
+    #[test]
+    fn digest_matches_alloy_signing_hash() {
+        let domain = domain();
+        let permit = permit();
+
+        assert_eq!(
+            eip712_digest(domain.separator(), &permit),
+            permit.eip712_signing_hash(&domain)
+        );
     }
 
-    fn _allowance(&self, owner: Address, spender: Address) -> U256 {
-        self.allowances.get(owner).get(spender)
+    #[test]
+    fn signed_permit_recovers_to_owner() {
+        let key = testing::key(1);
+        let domain = domain();
+        let permit = Permit {
+            owner: testing::address_of(key.verifying_key()),
+            ..permit()
+        };
+
+        let (v, r, s) = testing::sign_permit(&key, &domain, &permit);
+        assert_eq!(normalize_v(v), Some(v));
+        let digest = eip712_digest(domain.separator(), &permit);
+        assert_eq!(testing::recover(digest, v, r, s), Some(permit.owner));
     }
 
-    fn _approve(&mut self, spender: Address, amount: U256) -> Erc20Result<bool> {
-        self.set_approval(msg::sender(), spender, amount)?;
-        Ok(true)
+    #[test]
+    fn packed_signature_recovers_to_owner() {
+        let key = testing::key(2);
+        let domain = domain();
+        let permit = Permit {
+            owner: testing::address_of(key.verifying_key()),
+            ..permit()
+        };
+        let (v, r, s) = testing::sign_permit(&key, &domain, &permit);
+
+        let mut rs = r.to_be_bytes::<32>().to_vec();
+        rs.extend_from_slice(&s.to_be_bytes::<32>());
+        let (r, s) = split_rs(&rs).unwrap();
+        // EIP-155 style `v` for Arbitrum One, as `permit_packed` accepts.
+        let wide = U256::from(v - 27 + 35) + U256::from(42161 * 2);
+        let v = normalize_v_wide(wide).unwrap();
+
+        let digest = eip712_digest(domain.separator(), &permit);
+        assert_eq!(testing::recover(digest, v, r, s), Some(permit.owner));
     }
 
-    fn _transfer_from(&mut self, from: Address, to: Address, amount: U256) -> Erc20Result<bool> {
-        let spender = msg::sender();
-        let allowance = self._allowance(from, spender);
+    #[test]
+    fn altered_permit_recovers_to_someone_else() {
+        let key = testing::key(3);
+        let domain = domain();
+        let permit = Permit {
+            owner: testing::address_of(key.verifying_key()),
+            ..permit()
+        };
+        let (v, r, s) = testing::sign_permit(&key, &domain, &permit);
 
-        if allowance < amount {
-            return Err(Erc20::Erc20Errors::InsufficientAllowance(Default::default()));
-        }
-        self.set_approval(from, spender, allowance - amount)?;
-        self.move_tokens(from, to, amount)?;
+        let altered = Permit {
+            value: permit.value + U256::from(1),
+            ..permit.clone()
+        };
+        let digest = eip712_digest(domain.separator(), &altered);
+        assert_ne!(testing::recover(digest, v, r, s), Some(permit.owner));
+    }
 
-        Ok(true)
+    #[test]
+    fn multisig_account_hashes_abi_encoded_set() {
+        let a = address!("000000000000000000000000000000000000000a");
+        let b = address!("000000000000000000000000000000000000000b");
+
+        // abi.encode(address[], uint8): offset, threshold, length, members.
+        let mut encoded = vec![0u8; 32 * 5];
+        encoded[31] = 0x40;
+        encoded[63] = 2;
+        encoded[95] = 2;
+        encoded[108..128].copy_from_slice(a.as_slice());
+        encoded[140..160].copy_from_slice(b.as_slice());
+
+        assert_eq!(
+            multisig_account(&[a, b], 2),
+            Address::from_word(keccak256(encoded))
+        );
     }
 
-    fn _permit(
-        &mut self,
-        owner: Address,
-        spender: Address,
-        value: U256,
-        deadline: U256,
-        v: u8,
-        r: U256,
-        s: U256,
-    ) -> Erc20Result<()> {
-        if owner == Address::ZERO {
-            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
+    #[test]
+    fn multisig_account_depends_on_set_and_threshold() {
+        let a = address!("000000000000000000000000000000000000000a");
+        let b = address!("000000000000000000000000000000000000000b");
+
+        assert_ne!(multisig_account(&[a, b], 1), multisig_account(&[a, b], 2));
+        assert_ne!(multisig_account(&[a, b], 1), multisig_account(&[a], 1));
+    }
+
+    #[test]
+    fn permit_typehash_is_canonical() {
+        assert_eq!(
+            permit_typehash(),
+            b256!("6e71edae12b1b97f4d1f60370fef10105fa2faae0126114a169c64845d6126c9")
+        );
+    }
+
+    /// The ERC-165 id of an interface: the XOR of its selectors.
+    fn interface_id(signatures: &[&str]) -> [u8; 4] {
+        signatures.iter().fold([0; 4], |id, signature| {
+            let hash = keccak256(signature.as_bytes());
+            std::array::from_fn(|i| id[i] ^ hash[i])
+        })
+    }
+
+    #[test]
+    fn interface_ids_match_selectors() {
+        assert_eq!(
+            ERC165_INTERFACE_ID,
+            interface_id(&["supportsInterface(bytes4)"])
+        );
+        assert_eq!(
+            ERC20_INTERFACE_ID,
+            interface_id(&[
+                "totalSupply()",
+                "balanceOf(address)",
+                "transfer(address,uint256)",
+                "transferFrom(address,address,uint256)",
+                "approve(address,uint256)",
+                "allowance(address,address)",
+            ])
+        );
+        assert_eq!(
+            ERC2612_INTERFACE_ID,
+            interface_id(&[
+                "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+                "nonces(address)",
+                "DOMAIN_SEPARATOR()",
+            ])
+        );
+    }
+
+    #[test]
+    fn supports_erc165_and_erc20_only() {
+        assert!(is_supported_interface(ERC165_INTERFACE_ID));
+        assert!(is_supported_interface(ERC20_INTERFACE_ID));
+        assert!(!is_supported_interface(ERC2612_INTERFACE_ID));
+        assert!(!is_supported_interface([0xff; 4]));
+        assert!(!is_supported_interface([0; 4]));
+    }
+
+    #[test]
+    fn permit_failures_map_to_codes() {
+        let cases = [
+            (
+                Erc20Errors::PermitExpired(Erc20::PermitExpired {
+                    deadline: U256::from(1),
+                    blockTimestamp: U256::from(2),
+                }),
+                Some(permit_error::EXPIRED),
+            ),
+            (
+                Erc20Errors::PermitExpiredAtBlock(Erc20::PermitExpiredAtBlock {
+                    deadlineBlock: U256::from(1),
+                    blockNumber: U256::from(2),
+                }),
+                Some(permit_error::EXPIRED),
+            ),
+            (
+                Erc20Errors::DeadlineTooFar(Default::default()),
+                Some(permit_error::DEADLINE_TOO_FAR),
+            ),
+            (
+                Erc20Errors::InvalidPermit(Default::default()),
+                Some(permit_error::INVALID_SIGNATURE),
+            ),
+            (
+                Erc20Errors::InvalidOwner(Default::default()),
+                Some(permit_error::INVALID_OWNER),
+            ),
+            (
+                Erc20Errors::NonceAlreadyUsed(Default::default()),
+                Some(permit_error::NONCE_USED),
+            ),
+            (Erc20Errors::InsufficientBalance(Default::default()), None),
+            (Erc20Errors::InsufficientAllowance(Default::default()), None),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.permit_error_code(), code);
         }
-        if U256::from(block::timestamp()) > deadline {
-            return Err(Erc20::Erc20Errors::PermitExpired(Default::default()));
+    }
+
+    #[test]
+    fn permit_failures_encode_as_their_code() {
+        let encoded = Erc20Errors::InvalidOwner(Default::default()).encode();
+        if cfg!(feature = "granular-permit-errors") {
+            assert_eq!(encoded, Erc20::InvalidOwner {}.encode());
+        } else {
+            let code = permit_error::INVALID_OWNER;
+            assert_eq!(encoded, Erc20::PermitError { code }.encode());
         }
 
-        // Compute Permit signing hash
-        let permit = Permit {
-            owner,
-            spender,
-            value,
-            nonce: self.nonces.get(owner),
-            deadline,
+        assert_eq!(
+            Erc20Errors::InsufficientBalance(Default::default()).encode(),
+            Erc20::InsufficientBalance {}.encode()
+        );
+    }
+
+    #[test]
+    fn expired_permit_reports_deadline_and_timestamp() {
+        let deadline = U256::from(1_700_000_000);
+        let now = deadline + U256::from(1);
+        let Err(Erc20Errors::PermitExpired(error)) = check_deadline_at(deadline, now, None) else {
+            panic!("expected PermitExpired");
         };
-        let domain = self.get_domain();
-        let permit_hash = permit.eip712_signing_hash(&domain);
 
-        let recovered = ecrecover(permit_hash, v, r, s)
-            .map_err(|_| Erc20Errors::InvalidPermit(Default::default()))?;
+        let decoded = Erc20::PermitExpired::decode(&error.encode(), true).unwrap();
+        assert_eq!(decoded.deadline, deadline);
+        assert_eq!(decoded.blockTimestamp, now);
+    }
 
-        if recovered != owner {
-            return Err(Erc20::Erc20Errors::InvalidPermit(Default::default()));
-        }
+    #[test]
+    fn deadline_is_inclusive_and_bounded_by_window() {
+        let now = U256::from(1_700_000_000);
+        assert!(check_deadline_at(now, now, None).is_ok());
+        assert!(check_deadline_at(now + U256::from(60), now, Some(60)).is_ok());
+        assert!(matches!(
+            check_deadline_at(now + U256::from(61), now, Some(60)),
+            Err(Erc20Errors::DeadlineTooFar(_))
+        ));
+    }
 
-        self.set_approval(owner, spender, value)?;
-        self.increment_nonce(owner)?;
+    #[test]
+    fn u48_deadline_hashes_like_full_width() {
+        let key = testing::key(4);
+        let domain = domain();
+        let deadline: u64 = (1 << 48) - 1;
 
-        Ok(())
+        // The largest uint48, as a standard signer encodes it.
+        let mut word = [0u8; 32];
+        word[26..].fill(0xff);
+        let signed = Permit {
+            owner: testing::address_of(key.verifying_key()),
+            deadline: U256::from_be_bytes(word),
+            ..permit()
+        };
+        let (v, r, s) = testing::sign_permit(&key, &domain, &signed);
+
+        // As `permit_u48` widens it.
+        let widened = Permit {
+            deadline: U256::from(deadline),
+            ..signed.clone()
+        };
+        let digest = eip712_digest(domain.separator(), &widened);
+        assert_eq!(digest, signed.eip712_signing_hash(&domain));
+        assert_eq!(testing::recover(digest, v, r, s), Some(signed.owner));
     }
 
-    fn _transfer_with_permit(
-        &mut self,
-        to: Address,
-        amount: U256,
+    #[test]
+    fn merge_transfers_sorts_and_sums_duplicates() {
+        let a = address!("000000000000000000000000000000000000000a");
+        let b = address!("000000000000000000000000000000000000000b");
+        let c = address!("000000000000000000000000000000000000000c");
 
-        owner: Address,
-        spender: Address,
-        value: U256,
-        deadline: U256,
-        v: u8,
-        r: U256,
-        s: U256,
-    ) -> Erc20Result<bool> {
-        self._permit(owner, spender, value, deadline, v, r, s)?;
-        self._transfer_from(owner, to, amount)
+        let Ok(merged) = merge_transfers(
+            vec![c, a, b, a, c],
+            [5, 1, 2, 3, 4].map(U256::from).to_vec(),
+        ) else {
+            panic!("expected the transfers to merge");
+        };
+        assert_eq!(
+            merged,
+            vec![(a, U256::from(4)), (b, U256::from(2)), (c, U256::from(9))]
+        );
+    }
+
+    #[test]
+    fn merge_transfers_rejects_overflowing_sums() {
+        let a = address!("000000000000000000000000000000000000000a");
+        assert!(matches!(
+            merge_transfers(vec![a, a], vec![U256::MAX, U256::from(1)]),
+            Err(Erc20Errors::InsufficientBalance(_))
+        ));
+    }
+
+    #[test]
+    fn whole_units_splits_by_decimals() {
+        let amount = U256::from(1_234_567_890_123_456_789_u128);
+        assert_eq!(
+            whole_units(amount, 18),
+            (U256::from(1), U256::from(234_567_890_123_456_789_u128))
+        );
+        assert_eq!(
+            whole_units(amount, 6),
+            (U256::from(1_234_567_890_123_u128), U256::from(456_789))
+        );
+        assert_eq!(
+            whole_units(U256::from(10).pow(U256::from(18)), 18),
+            (U256::from(1), U256::ZERO)
+        );
+    }
+
+    #[test]
+    fn whole_units_handles_zero_and_excessive_decimals() {
+        let amount = U256::from(1_234_567);
+        assert_eq!(whole_units(amount, 0), (amount, U256::ZERO));
+        assert_eq!(whole_units(U256::ZERO, 0), (U256::ZERO, U256::ZERO));
+        // 10^78 doesn't fit in a `U256`, so nothing reaches one whole token.
+        assert_eq!(whole_units(U256::MAX, 78), (U256::ZERO, U256::MAX));
+        assert_eq!(whole_units(U256::MAX, 77).0, U256::from(1));
+    }
+
+    #[test]
+    fn permit_parties_reject_zero_addresses() {
+        let owner = address!("2222222222222222222222222222222222222222");
+        let spender = address!("3333333333333333333333333333333333333333");
+
+        assert!(check_permit_parties(owner, &[spender]).is_ok());
+        assert!(matches!(
+            check_permit_parties(Address::ZERO, &[spender]),
+            Err(Erc20Errors::InvalidOwner(_))
+        ));
+        assert!(matches!(
+            check_permit_parties(owner, &[spender, Address::ZERO]),
+            Err(Erc20Errors::InvalidPermit(_))
+        ));
+    }
+
+    #[test]
+    fn precomputed_separator_matches_manual_encoding() {
+        // `_init` caches `Eip712Domain::separator`, which must agree with the
+        // separator a verifier computes from the domain fields.
+        let domain = domain();
+        let typehash = keccak256(
+            "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)",
+        );
+
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&typehash[..]);
+        encoded.extend_from_slice(&keccak256("my dumb token")[..]);
+        encoded.extend_from_slice(&keccak256("1")[..]);
+        encoded.extend_from_slice(&U256::from(42161).to_be_bytes::<32>());
+        encoded.extend_from_slice(&[0; 12]);
+        encoded.extend_from_slice(&[0x11; 20]);
+        assert_eq!(domain.separator(), keccak256(encoded));
     }
 }
@@ -6,21 +6,99 @@ extern crate alloc;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-use alloy_primitives::{fixed_bytes, FixedBytes};
-use erc20permit::{DomainInfo, Erc20Details, Erc20Permit};
-use stylus_sdk::stylus_proc::{entrypoint, external, sol_storage};
+use alloc::vec::Vec;
+use alloy_primitives::{address, fixed_bytes, uint, Address, FixedBytes, U256};
+use erc20permit::{
+    unknown_selector, DomainInfo, Erc20Details, Erc20Permit, TransferFee, TransferHook,
+    ERC2612_INTERFACE_ID,
+};
+use stylus_sdk::{
+    abi::Router,
+    function_selector,
+    storage::{StorageType, TopLevelStorage},
+    stylus_proc::{entrypoint, external, sol_storage},
+    ArbResult,
+};
 
 mod ecrecover;
+mod erc1363;
 mod erc20permit;
+mod math;
+#[cfg(test)]
+mod testing;
+mod token;
 
 sol_storage! {
-    #[entrypoint]
     pub struct MyErc20PermitContract {
         #[borrow]
         Erc20Permit<MyDomain, MyDetails> erc20;
     }
 }
 
+unsafe impl TopLevelStorage for MyErc20PermitContract {}
+
+/// Canonical ERC-2612 selectors that permit tooling calls, and the selectors
+/// they are routed to. The SDK camel-cases method names and `permit` types its
+/// signature words as `uint256`, but the calldata encodes identically.
+const ALIASES: [([u8; 4], [u8; 4]); 3] = [
+    (
+        function_selector!("DOMAIN_SEPARATOR"),
+        function_selector!("domainSeparator"),
+    ),
+    (
+        function_selector!("PERMIT_TYPEHASH"),
+        function_selector!("permitTypehash"),
+    ),
+    (
+        function_selector!(
+            "permit",
+            Address,
+            Address,
+            U256,
+            U256,
+            u8,
+            FixedBytes<32>,
+            FixedBytes<32>
+        ),
+        function_selector!("permit", Address, Address, U256, U256, u8, U256, U256),
+    ),
+];
+
+/// Inherited selectors this contract doesn't expose. It initializes through
+/// `genesis`, so `init` is hidden. `multicall` and `execute` route nested
+/// calls past this entrypoint, where `init` stays reachable; it still fails,
+/// as `MyDetails` sets no `INITIALIZER`.
+const HIDDEN: [[u8; 4]; 1] = [function_selector!("init", Address, U256, Address)];
+
+/// Routes calldata to `MyErc20PermitContract`. Unlike the default entrypoint,
+/// calls to selectors it doesn't implement revert with a decodable
+/// `UnknownSelector` error rather than empty data, and the canonical
+/// ERC-2612 selectors in `ALIASES` are accepted.
+#[entrypoint]
+fn route(input: Vec<u8>) -> ArbResult {
+    let mut storage = unsafe { MyErc20PermitContract::new(U256::ZERO, 0) };
+    if let Some(&selector) = input.first_chunk::<4>().filter(|s| !HIDDEN.contains(s)) {
+        let routed_as = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == selector)
+            .map_or(selector, |(_, target)| *target);
+        let routed = <MyErc20PermitContract as Router<_>>::route(
+            &mut storage,
+            u32::from_be_bytes(routed_as),
+            &input[4..],
+        );
+        if let Some(result) = routed {
+            return result;
+        }
+    }
+    Err(unknown_selector(&input))
+}
+
+#[cfg(feature = "export-abi")]
+fn main() {
+    stylus_sdk::abi::export::print_abi::<MyErc20PermitContract>();
+}
+
 pub struct MyDomain;
 
 impl DomainInfo for MyDomain {
@@ -41,8 +119,50 @@ impl Erc20Details for MyDetails {
     const SYMBOL: &'static str = "MDT";
 
     const DECIMALS: u8 = 18;
+
+    const BURN_SINKS: &'static [Address] = &[address!("000000000000000000000000000000000000dead")];
+}
+
+impl TransferHook for MyDetails {}
+
+impl TransferFee for MyDetails {}
+
+/// Supply minted once to bootstrap the contract.
+pub trait Genesis {
+    /// Receives the genesis supply, and becomes the owner.
+    const GENESIS_HOLDER: Address;
+    const GENESIS_SUPPLY: U256;
+}
+
+impl Genesis for MyErc20PermitContract {
+    const GENESIS_HOLDER: Address = address!("deadbeefdeadbeefdeadbeefdeadbeefdeadbeef");
+
+    const GENESIS_SUPPLY: U256 = uint!(1_000_000_000_000_000_000_000_000_U256);
 }
 
 #[external]
 #[inherit(Erc20Permit<MyDomain, MyDetails>)]
-impl MyErc20PermitContract {}
+impl MyErc20PermitContract {
+    /// Mints `GENESIS_SUPPLY` to `GENESIS_HOLDER` and makes them the owner.
+    /// Stylus has no constructors, so call this once after deployment. Anyone
+    /// may call it, as the holder and supply are fixed.
+    pub fn genesis(&mut self) -> Result<(), Vec<u8>> {
+        self.erc20
+            ._init(
+                Self::GENESIS_HOLDER,
+                Self::GENESIS_SUPPLY,
+                Self::GENESIS_HOLDER,
+            )
+            .map_err(|e| e.encode())
+    }
+
+    /// ERC-165, adding ERC-2612 to the inherited ids, as `route` accepts its
+    /// canonical selectors. Calls nested in `multicall` reach the inherited
+    /// method instead.
+    pub fn supports_interface(&self, interface_id: FixedBytes<4>) -> Result<bool, Vec<u8>> {
+        Ok(
+            interface_id.0 == ERC2612_INTERFACE_ID
+                || self.erc20.supports_interface(interface_id)?,
+        )
+    }
+}
@@ -0,0 +1,153 @@
+use alloy_primitives::{U256, U512};
+
+/// `x * y / denominator`, rounded down. The product is computed in 512 bits,
+/// so it only overflows if the result does.
+///
+/// Panics if `denominator` is zero or the result does not fit in 256 bits.
+pub fn mul_div_floor(x: U256, y: U256, denominator: U256) -> U256 {
+    mul_div(x, y, denominator).0
+}
+
+/// `mul_div_floor`, returning `None` rather than panicking if the result does
+/// not fit in 256 bits.
+///
+/// Panics if `denominator` is zero.
+pub fn checked_mul_div_floor(x: U256, y: U256, denominator: U256) -> Option<U256> {
+    let product: U512 = x.widening_mul(y);
+    let quotient = product / U512::from(denominator);
+    (quotient <= U512::from(U256::MAX)).then(|| quotient.to::<U256>())
+}
+
+/// `x * y / denominator`, rounded up.
+///
+/// Panics if `denominator` is zero or the result does not fit in 256 bits.
+pub fn mul_div_ceil(x: U256, y: U256, denominator: U256) -> U256 {
+    let (quotient, rounded) = mul_div(x, y, denominator);
+    if rounded {
+        quotient
+            .checked_add(U256::from(1))
+            .expect("mul_div_ceil overflow")
+    } else {
+        quotient
+    }
+}
+
+/// Returns the floored quotient, and whether it was rounded.
+fn mul_div(x: U256, y: U256, denominator: U256) -> (U256, bool) {
+    let product: U512 = x.widening_mul(y);
+    let (quotient, remainder) = product.div_rem(U512::from(denominator));
+    (quotient.to::<U256>(), remainder != U512::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn u256(bytes: [u8; 32]) -> U256 {
+        U256::from_be_bytes(bytes)
+    }
+
+    /// `x * y` and `denominator` widened, computed without `widening_mul`.
+    fn reference(x: U256, y: U256, denominator: U256) -> (U512, U512) {
+        let product = U512::from(x) * U512::from(y);
+        let denominator = U512::from(denominator);
+        (product / denominator, product % denominator)
+    }
+
+    proptest! {
+        #[test]
+        fn floor_matches_reference(x: [u8; 32], y: [u8; 32], d: [u8; 32]) {
+            let (x, y, d) = (u256(x), u256(y), u256(d));
+            prop_assume!(d != U256::ZERO);
+            let (quotient, _) = reference(x, y, d);
+            prop_assume!(quotient <= U512::from(U256::MAX));
+
+            prop_assert_eq!(U512::from(mul_div_floor(x, y, d)), quotient);
+        }
+
+        #[test]
+        fn ceil_matches_reference(x: [u8; 32], y: [u8; 32], d: [u8; 32]) {
+            let (x, y, d) = (u256(x), u256(y), u256(d));
+            prop_assume!(d != U256::ZERO);
+            let (quotient, remainder) = reference(x, y, d);
+            let expected = if remainder == U512::ZERO {
+                quotient
+            } else {
+                quotient + U512::from(1)
+            };
+
+            let ceil = std::panic::catch_unwind(|| mul_div_ceil(x, y, d));
+            if expected <= U512::from(U256::MAX) {
+                prop_assert_eq!(U512::from(ceil.unwrap()), expected);
+            } else {
+                prop_assert!(ceil.is_err());
+            }
+        }
+
+        #[test]
+        fn ceil_is_floor_or_one_more(x: u128, y: u128, d: u128) {
+            let (x, y, d) = (U256::from(x), U256::from(y), U256::from(d));
+            prop_assume!(d != U256::ZERO);
+            let floor = mul_div_floor(x, y, d);
+            let ceil = mul_div_ceil(x, y, d);
+
+            prop_assert!(ceil == floor || ceil == floor + U256::from(1));
+            prop_assert_eq!(ceil == floor, (x * y) % d == U256::ZERO);
+        }
+    }
+
+    #[test]
+    fn full_width_product() {
+        // The product overflows 256 bits, but the result doesn't.
+        assert_eq!(mul_div_floor(U256::MAX, U256::MAX, U256::MAX), U256::MAX);
+        assert_eq!(
+            mul_div_ceil(U256::MAX, U256::MAX - U256::from(1), U256::MAX),
+            U256::MAX - U256::from(1)
+        );
+        assert_eq!(
+            mul_div_ceil(U256::MAX, U256::from(1), U256::from(2)),
+            U256::MAX / U256::from(2) + U256::from(1)
+        );
+    }
+
+    /// `x * y / d` with a floor of exactly `U256::MAX` and a remainder.
+    fn max_floor_with_remainder() -> (U256, U256, U256) {
+        let x = U256::from_be_bytes([
+            0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d,
+            0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb, 0x6d, 0xb6, 0xdb,
+            0x6d, 0xb6, 0xdb, 0x6d,
+        ]);
+        (x, U256::from(7), U256::from(6))
+    }
+
+    #[test]
+    fn floor_at_max_with_remainder() {
+        let (x, y, d) = max_floor_with_remainder();
+        assert_eq!(mul_div_floor(x, y, d), U256::MAX);
+        assert_eq!(checked_mul_div_floor(x, y, d), Some(U256::MAX));
+    }
+
+    #[test]
+    fn checked_floor_rejects_overflow() {
+        let (x, y, d) = max_floor_with_remainder();
+        assert_eq!(checked_mul_div_floor(x, y + U256::from(1), d), None);
+        assert_eq!(
+            checked_mul_div_floor(U256::MAX, U256::MAX, U256::from(1)),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mul_div_ceil overflow")]
+    fn ceil_past_max_panics() {
+        let (x, y, d) = max_floor_with_remainder();
+        mul_div_ceil(x, y, d);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_denominator_panics() {
+        mul_div_floor(U256::from(1), U256::from(1), U256::ZERO);
+    }
+}
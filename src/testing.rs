@@ -0,0 +1,43 @@
+//! Fixtures for tests that need real permit signatures.
+
+use alloy_primitives::{keccak256, Address, FixedBytes, U256};
+use alloy_sol_types::{Eip712Domain, SolStruct};
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+
+use crate::erc20permit::Permit;
+
+/// A signing key from a fixed 32-byte secret.
+pub fn key(secret: u8) -> SigningKey {
+    SigningKey::from_bytes(&[secret; 32].into()).expect("valid secret")
+}
+
+/// The account `key` signs for.
+pub fn address_of(key: &VerifyingKey) -> Address {
+    let point = key.to_encoded_point(false);
+    Address::from_slice(&keccak256(&point.as_bytes()[1..])[12..])
+}
+
+/// Signs `permit` under `domain` as `key`, returning `(v, r, s)` with `v` as
+/// 27 or 28 and a low `s`, as `permit` expects. The digest is alloy's, not
+/// the contract's, so tests can check one against the other.
+pub fn sign_permit(key: &SigningKey, domain: &Eip712Domain, permit: &Permit) -> (u8, U256, U256) {
+    let digest = permit.eip712_signing_hash(domain);
+    let (sig, recid) = key
+        .sign_prehash_recoverable(digest.as_slice())
+        .expect("signing a 32-byte digest");
+    let (r, s) = sig.split_bytes();
+    (
+        27 + recid.to_byte(),
+        U256::from_be_bytes::<32>(r.into()),
+        U256::from_be_bytes::<32>(s.into()),
+    )
+}
+
+/// The account that signed `digest`, or `None` if the signature is malformed.
+pub fn recover(digest: FixedBytes<32>, v: u8, r: U256, s: U256) -> Option<Address> {
+    let recid = RecoveryId::from_byte(v.checked_sub(27)?)?;
+    let sig = Signature::from_scalars(r.to_be_bytes::<32>(), s.to_be_bytes::<32>()).ok()?;
+    VerifyingKey::recover_from_prehash(digest.as_slice(), &sig, recid)
+        .ok()
+        .map(|key| address_of(&key))
+}
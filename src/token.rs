@@ -0,0 +1,164 @@
+use alloy_primitives::{b256, Address, B256, U256};
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::{
+    call::{self, Call},
+    types::AddressVM,
+};
+
+sol! {
+    function balanceOf(address account) external view returns (uint256);
+    function allowance(address owner, address spender) external view returns (uint256);
+    function transfer(address to, uint256 amount) external returns (bool);
+    function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external;
+}
+
+/// `keccak256("")`, the codehash of an existing account without code.
+pub const KECCAK_EMPTY: B256 =
+    b256!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+
+/// Whether `account` has code. `EXTCODEHASH` is zero for accounts that don't
+/// exist and `KECCAK_EMPTY` for those that do but have no code, e.g. funded
+/// EOAs.
+pub fn has_code(account: Address) -> bool {
+    matches!(account.codehash(), Some(hash) if hash != KECCAK_EMPTY)
+}
+
+/// Call `balanceOf` on a foreign ERC20. Errors unless the token returns a
+/// full word.
+pub fn balance_of(token: Address, account: Address) -> Result<U256, stylus_sdk::call::Error> {
+    let calldata = balanceOfCall { account }.encode();
+    let ret = call::static_call(Call::new(), token, &calldata)?;
+    Ok(balanceOfCall::decode_returns(&ret, true)?._0)
+}
+
+/// Call `allowance` on a foreign ERC20. Errors unless the token returns a
+/// full word.
+pub fn allowance(
+    token: Address,
+    owner: Address,
+    spender: Address,
+) -> Result<U256, stylus_sdk::call::Error> {
+    let calldata = allowanceCall { owner, spender }.encode();
+    let ret = call::static_call(Call::new(), token, &calldata)?;
+    Ok(allowanceCall::decode_returns(&ret, true)?._0)
+}
+
+/// Call `transfer` on a foreign ERC20. Returns whether the token reported
+/// success. Calls to accounts without code are treated as failures, as they
+/// would otherwise trivially succeed.
+pub fn transfer(
+    token: Address,
+    to: Address,
+    amount: U256,
+) -> Result<bool, stylus_sdk::call::Error> {
+    if !has_code(token) {
+        return Ok(false);
+    }
+    let calldata = transferCall { to, amount }.encode();
+
+    call::call(Call::new(), token, &calldata).map(|ret| succeeded(&ret))
+}
+
+/// Call `transferFrom` on a foreign ERC20, with the same success rules as
+/// `transfer`.
+pub fn transfer_from(
+    token: Address,
+    from: Address,
+    to: Address,
+    amount: U256,
+) -> Result<bool, stylus_sdk::call::Error> {
+    if !has_code(token) {
+        return Ok(false);
+    }
+    let calldata = transferFromCall { from, to, amount }.encode();
+
+    call::call(Call::new(), token, &calldata).map(|ret| succeeded(&ret))
+}
+
+/// Call EIP-2612 `permit` on a foreign ERC20. Returns whether the call
+/// succeeded.
+pub fn permit(
+    token: Address,
+    owner: Address,
+    spender: Address,
+    value: U256,
+    deadline: U256,
+    v: u8,
+    r: U256,
+    s: U256,
+) -> Result<bool, stylus_sdk::call::Error> {
+    if !has_code(token) {
+        return Ok(false);
+    }
+    let calldata = permitCall {
+        owner,
+        spender,
+        value,
+        deadline,
+        v,
+        r: r.to_be_bytes(),
+        s: s.to_be_bytes(),
+    }
+    .encode();
+
+    call::call(Call::new(), token, &calldata).map(|_| true)
+}
+
+/// As `permit`, but also succeeds if the call fails while `spender` is
+/// already allowed `value`. Permits are public once signed, so anyone can
+/// submit one first to make the nonce stale; this keeps that from blocking
+/// the caller.
+pub fn permit_or_allowed(
+    token: Address,
+    owner: Address,
+    spender: Address,
+    value: U256,
+    deadline: U256,
+    v: u8,
+    r: U256,
+    s: U256,
+) -> bool {
+    if matches!(
+        permit(token, owner, spender, value, deadline, v, r, s),
+        Ok(true)
+    ) {
+        return true;
+    }
+    matches!(allowance(token, owner, spender), Ok(allowed) if allowed >= value)
+}
+
+/// Tokens that predate the ERC20 return value return nothing on success.
+fn succeeded(ret: &[u8]) -> bool {
+    ret.is_empty() || (ret.len() >= 32 && ret[31] == 1 && ret[..31].iter().all(|b| *b == 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(last: u8) -> Vec<u8> {
+        let mut word = vec![0u8; 32];
+        word[31] = last;
+        word
+    }
+
+    #[test]
+    fn empty_return_succeeds() {
+        assert!(succeeded(&[]));
+    }
+
+    #[test]
+    fn true_word_succeeds() {
+        assert!(succeeded(&word(1)));
+        assert!(succeeded(&[word(1), vec![0xff]].concat()));
+    }
+
+    #[test]
+    fn other_returns_fail() {
+        assert!(!succeeded(&word(0)));
+        assert!(!succeeded(&word(2)));
+        assert!(!succeeded(&[[1u8; 1].as_slice(), &word(1)[1..]].concat()));
+        assert!(!succeeded(&[1]));
+    }
+}